@@ -1,3 +1,6 @@
+// `a_feature` is only there to show feature-gated links.
+#![allow(unknown_lints, unexpected_cfgs)]
+
 #[macro_use]
 extern crate error_chain;
 
@@ -21,18 +21,14 @@ fn main() {
     println!("      ErrorKind::Msg: {}", size_of_val(&msg));
     println!("        String: {}", size_of::<String>());
     println!("    State: {}", size_of::<error_chain::State>());
+    let state = error_chain::State::default();
+    println!("      State.next_error: {}", size_of_val(&state.next_error));
+    println!("      State.global_context: {}", size_of_val(&state.global_context));
+    println!("      State.extras: {}", size_of_val(&state.extras));
     #[cfg(feature = "backtrace")]
-    {
-        let state = error_chain::State {
-            next_error: None,
-            backtrace: None,
-        };
-        println!("      State.next_error: {}", size_of_val(&state.next_error));
-        println!("      State.backtrace: {}", size_of_val(&state.backtrace));
-    }
-    #[cfg(not(feature = "backtrace"))]
-    {
-        let state = error_chain::State { next_error: None };
-        println!("      State.next_error: {}", size_of_val(&state.next_error));
-    }
+    println!("      State.backtrace: {}", size_of_val(&state.backtrace));
+    #[cfg(feature = "context-trace")]
+    println!("      State.context_trace: {}", size_of_val(&state.context_trace));
+    #[cfg(feature = "timestamp")]
+    println!("      State.created_at: {}", size_of_val(&state.created_at));
 }
@@ -1,20 +1,29 @@
 /// Prefer to use `error_chain` instead of this macro.
 #[macro_export]
 macro_rules! error_chain_processed {
-    // Default values for `types` and `derive`.
+    // With a lifetime.
+    (
+        types {
+            $error_name:ident < $lt:lifetime >, $( $rest_types:tt )*
+        }
+        $( $rest: tt )*
+    ) => {
+        error_chain_borrowed! {
+            types {
+                $error_name < $lt >, $( $rest_types )*
+            }
+            $( $rest )*
+        }
+    };
+    // Default values for `types`.
     (
-        // FIXME does not work if either types or derive isn't empty
         types {}
-        derive {}
         $( $rest: tt )*
-    ) =>{
+    ) => {
         error_chain_processed! {
             types {
                 Error, ErrorKind, ResultExt, Result;
             }
-            derive {
-                Debug;
-            }
             $( $rest )*
         }
     };
@@ -26,7 +35,6 @@ macro_rules! error_chain_processed {
         }
         $( $rest: tt )*
     ) => {
-        log_syntax!(rest: $($rest)*);
         error_chain_processed! {
             types {
                 $error_name, $error_kind_name,
@@ -36,7 +44,27 @@ macro_rules! error_chain_processed {
         }
         /// Convenient wrapper around `std::Result`.
         #[allow(unused)]
-        pub type $result_name<T> = ::std::result::Result<T, $error_name>;
+        pub type $result_name<T, E = $error_name> = ::std::result::Result<T, E>;
+    };
+    // Default values for `derive`.
+    (
+        types {
+            $error_name:ident, $error_kind_name:ident,
+            $result_ext_name:ident;
+        }
+        derive {}
+        $( $rest: tt )*
+    ) => {
+        error_chain_processed! {
+            types {
+                $error_name, $error_kind_name,
+                $result_ext_name;
+            }
+            derive {
+                Debug;
+            }
+            $( $rest )*
+        }
     };
     // Without `Result` wrapper.
     (
@@ -49,20 +77,51 @@ macro_rules! error_chain_processed {
             $($trait:ident),*;
         }
 
+        allow {
+            $( ( $( $allow:tt )* ) )*
+        }
+
         links {
             $( $link_variant:ident ( $link_error_path:path, $link_kind_path:path )
                $( #[$meta_links:meta] )*; ) *
         }
 
+        flat_links {
+            $( $flat_link_variant:ident ( $flat_link_error_path:path, $flat_link_kind_path:path )
+               $( #[$meta_flat_links:meta] )*; ) *
+        }
+
         foreign_links {
             $( $foreign_link_variant:ident ( $foreign_link_error_path:path )
+               $( context($foreign_link_context:expr) )?
+               [$( $foreign_link_no_backtrace:ident )?]
                $( #[$meta_foreign_links:meta] )*; )*
         }
 
+        mapped_foreign_links {
+            $( $mapped_link_variant:ident ( $mapped_link_error_path:path )
+               as $mapped_link_kind_name:ident :: $mapped_link_kind_variant:ident
+               $( #[$meta_mapped_links:meta] )*; )*
+        }
+
+        transparent_foreign_links {
+            $( $transparent_link_variant:ident ( $transparent_link_error_path:path )
+               $( #[$meta_transparent_links:meta] )*; )*
+        }
+
         errors {
             $( $error_chunks:tt ) *
         }
 
+        options {
+            $( $option:tt )*
+        }
+
+        groups {
+            $( $group_variant:ident ( $group_kind_name:ident )
+               $( #[$meta_groups:meta] )* { $( $group_chunks:tt )* } )*
+        }
+
     ) => {
         /// The Error type.
         ///
@@ -73,7 +132,7 @@ macro_rules! error_chain_processed {
         ///   internals, containing:
         ///   - a backtrace, generated when the error is created.
         ///   - an error chain, used for the implementation of `Error::cause()`.
-        #[derive(Debug)]
+        $( #[allow($( $allow )*)] )*
         pub struct $error_name(
             // The members must be `pub` for `links`.
             /// The kind of the error.
@@ -90,10 +149,15 @@ macro_rules! error_chain_processed {
                 $error_name(kind, state)
             }
 
+            fn into_parts(self) -> ($error_kind_name, $crate::State) {
+                (self.0, self.1)
+            }
+
             fn from_kind(kind: Self::ErrorKind) -> Self {
                 Self::from_kind(kind)
             }
 
+            #[track_caller]
             fn with_chain<E, K>(error: E, kind: K)
                 -> Self
                 where E: ::std::error::Error + Send + 'static,
@@ -106,10 +170,11 @@ macro_rules! error_chain_processed {
                 self.kind()
             }
 
-            fn iter(&self) -> $crate::ErrorChainIter {
+            fn iter(&self) -> $crate::ErrorChainIter<'_> {
                 $crate::ErrorChainIter(Some(self))
             }
 
+            #[track_caller]
             fn chain_err<F, EK>(self, error: F) -> Self
                 where F: FnOnce() -> EK,
                       EK: Into<$error_kind_name> {
@@ -120,9 +185,52 @@ macro_rules! error_chain_processed {
                 self.backtrace()
             }
 
+            fn backtraces(&self) -> Vec<&$crate::Backtrace> {
+                self.backtraces()
+            }
+
+            fn thread(&self) -> Option<(Option<String>, ::std::thread::ThreadId)> {
+                self.thread()
+            }
+
+            fn created_at(&self) -> Option<::std::time::SystemTime> {
+                self.created_at()
+            }
+
+            fn context_trace(&self) -> &[(&'static str, u32)] {
+                self.context_trace()
+            }
+
+            fn global_context(&self) -> &[String] {
+                self.global_context()
+            }
+
+            fn extras(&self) -> Option<&$crate::StateExtras> {
+                self.1.extras.as_ref().map(|extras| &**extras)
+            }
+
+            fn has_foreign_root(&self) -> bool {
+                self.has_foreign_root()
+            }
+
+            fn kind_path(&self) -> String {
+                self.kind_path()
+            }
+
+            fn kind_name(&self) -> &'static str {
+                self.0.variant_name()
+            }
+
+            fn push_context(&mut self, file: &'static str, line: u32) {
+                self.1.push_context(file, line)
+            }
+
             impl_extract_backtrace!($error_name
                                     $error_kind_name
                                     $([$link_error_path, $(#[$meta_links])*])*);
+
+            impl_extract_context_trace!($error_name
+                                        $([$link_error_path, $(#[$meta_links])*])*);
         }
 
         #[allow(dead_code)]
@@ -131,19 +239,32 @@ macro_rules! error_chain_processed {
             pub fn from_kind(kind: $error_kind_name) -> $error_name {
                 $error_name(
                     kind,
-                    $crate::State::default(),
+                    $crate::State::default().with_options::<$error_kind_name>(),
                 )
             }
 
+            /// Constructs an error from a message, as the kind chosen by the
+            /// `bail_kind(...)` option, or `Msg` by default. This is how
+            /// `String`s are converted into the error, as by `bail!` with a
+            /// formatted string.
+            pub fn from_message(message: String) -> $error_name {
+                $error_name::from_kind($crate::ErrorChainOptions::bail_kind(message))
+            }
+
             /// Constructs a chained error from another error and a kind, and generates a backtrace.
+            #[track_caller]
             pub fn with_chain<E, K>(error: E, kind: K)
                 -> $error_name
                 where E: ::std::error::Error + Send + 'static,
                       K: Into<$error_kind_name>
             {
+                let location = ::std::panic::Location::caller();
+                let mut state = $crate::State::new::<$error_name>(Box::new(error), )
+                    .with_options::<$error_kind_name>();
+                state.push_context(location.file(), location.line());
                 $error_name(
                     kind.into(),
-                    $crate::State::new::<$error_name>(Box::new(error), ),
+                    state,
                 )
             }
 
@@ -153,20 +274,202 @@ macro_rules! error_chain_processed {
             }
 
             /// Iterates over the error chain.
-            pub fn iter(&self) -> $crate::ErrorChainIter {
+            pub fn iter(&self) -> $crate::ErrorChainIter<'_> {
                 $crate::ChainedError::iter(self)
             }
 
+            /// Returns the number of errors in the chain, including this one.
+            pub fn chain_len(&self) -> usize {
+                $crate::ChainedError::chain_len(self)
+            }
+
+            /// Returns `true` if the kinds of this error and `other` are the
+            /// same variant. See `ChainedError::same_kind_as`.
+            pub fn same_kind_as(&self, other: &$error_name) -> bool {
+                $crate::ChainedError::same_kind_as(self, other)
+            }
+
+            /// Returns the error at `depth` in the chain. See
+            /// `ChainedError::error_at`.
+            pub fn error_at(&self, depth: usize) -> Option<&dyn (::std::error::Error)> {
+                $crate::ChainedError::error_at(self, depth)
+            }
+
             /// Returns the backtrace associated with this error.
             pub fn backtrace(&self) -> Option<&$crate::Backtrace> {
                 self.1.backtrace()
             }
 
+            /// Captures a backtrace if this error has none, even if
+            /// `RUST_BACKTRACE` is not set, so that handlers can make sure
+            /// that the errors they report have one. Does nothing if the
+            /// `backtrace` feature is disabled.
+            pub fn ensure_backtrace(&mut self) {
+                self.1.force_backtrace();
+            }
+
+            /// Returns the backtrace associated with this error, followed by the
+            /// backtraces captured when chaining onto it if
+            /// `keep_all_backtraces` is enabled.
+            pub fn backtraces(&self) -> Vec<&$crate::Backtrace> {
+                self.1.backtraces()
+            }
+
+            /// Returns the name and id of the thread the backtrace was captured on.
+            pub fn thread(&self) -> Option<(Option<String>, ::std::thread::ThreadId)> {
+                self.1.thread()
+            }
+
+            /// Returns the time at which this error was created, if the
+            /// `timestamp` feature is enabled.
+            pub fn created_at(&self) -> Option<::std::time::SystemTime> {
+                self.1.created_at()
+            }
+
+            /// Returns the locations of the `chain_err` and `bail!` calls which
+            /// built this error.
+            pub fn context_trace(&self) -> &[(&'static str, u32)] {
+                self.1.context_trace()
+            }
+
+            /// Returns the global contexts which were pushed when this error
+            /// was created.
+            pub fn global_context(&self) -> &[String] {
+                self.1.global_context()
+            }
+
+            /// Returns the key-value pairs given to `ResultExt::chain_err_kv`
+            /// for this error and its causes of the same family, from the top
+            /// of the chain to the root.
+            pub fn context_kvs(&self) -> Vec<(&'static str, String)> {
+                let mut kvs = Vec::new();
+                let mut next: Option<&(dyn (::std::error::Error) + 'static)> = Some(self);
+                while let Some(e) = next {
+                    if let Some(e) = e.downcast_ref::<$error_name>() {
+                        if let Some(kv) = e.1.extras.as_ref().and_then(|x| x.context_kv.as_ref()) {
+                            kvs.push((kv.0, kv.1.clone()));
+                        }
+                    }
+                    next = e.source();
+                }
+                kvs
+            }
+
             /// Extends the error chain with a new entry.
+            #[track_caller]
             pub fn chain_err<F, EK>(self, error: F) -> $error_name
                 where F: FnOnce() -> EK, EK: Into<$error_kind_name> {
                 $error_name::with_chain(self, Self::from_kind(error().into()))
             }
+
+            /// Makes this error the cause of a new error of kind `kind`. The
+            /// whole chain is kept as the causes of the new error, which
+            /// carries over the backtrace.
+            #[track_caller]
+            pub fn rewrap(self, kind: $error_kind_name) -> $error_name {
+                $error_name::with_chain(self, kind)
+            }
+
+            /// Adds a new error of kind `kind` as the deepest cause of this
+            /// error, to annotate the root cause, e.g. with the setting which
+            /// originally triggered it, rather than the top of the chain.
+            ///
+            /// The causes which are not errors of this family can't be given
+            /// a cause, so if the chain ends with such errors, the
+            /// new error is inserted just above them instead.
+            pub fn prepend_root<EK>(mut self, kind: EK) -> $error_name
+                where EK: Into<$error_kind_name>
+            {
+                fn splice(e: &mut $error_name, mut root: $error_name) {
+                    if let Some(ref mut next) = e.1.next_error {
+                        if let Some(next) = next.downcast_mut::<$error_name>() {
+                            return splice(next, root);
+                        }
+                    }
+                    root.1.next_error = e.1.next_error.take();
+                    e.1.next_error = Some(Box::new(root));
+                }
+
+                splice(&mut self, $error_name::from_kind(kind.into()));
+                self
+            }
+
+            /// Returns the dotted path of nested kind variant names. See
+            /// `ChainedError::kind_path`.
+            pub fn kind_path(&self) -> String {
+                self.0.kind_path()
+            }
+
+            /// Returns `true` if the rootmost error of the chain is not an
+            /// error of this family. See
+            /// `ChainedError::has_foreign_root` for the limitations.
+            #[allow(unknown_lints, unused_doc_comments)]
+            pub fn has_foreign_root(&self) -> bool {
+                match self.1.next_error {
+                    Some(ref e) => match e.downcast_ref::<$error_name>() {
+                        Some(e) => e.has_foreign_root(),
+                        None => true,
+                    },
+                    None => match self.0 {
+                        $(
+                            $(#[$meta_foreign_links])*
+                            $error_kind_name::$foreign_link_variant(_) => true,
+                        ) *
+                        _ => false,
+                    },
+                }
+            }
+
+            /// Replaces the cause of this error with the result of `f`, which
+            /// is given the current cause.
+            pub fn map_cause<F>(mut self, f: F) -> $error_name
+                where F: FnOnce(Option<Box<dyn (::std::error::Error) + Send>>)
+                                -> Option<Box<dyn (::std::error::Error) + Send>> {
+                self.1.next_error = f(self.1.next_error.take());
+                self
+            }
+
+            /// Builds an error from its parts, without capturing a backtrace.
+            pub fn from_parts(parts: $crate::ErrorParts<$error_kind_name>) -> $error_name {
+                $error_name(parts.kind, $crate::State::from_parts(parts.cause, parts.backtrace))
+            }
+
+            /// Splits the error into its parts. The backtraces captured when
+            /// chaining and the context trace are dropped.
+            pub fn into_parts(self) -> $crate::ErrorParts<$error_kind_name> {
+                let (cause, backtrace) = self.1.into_parts();
+                $crate::ErrorParts {
+                    kind: self.0,
+                    cause,
+                    backtrace,
+                }
+            }
+
+            /// Removes the cause and the backtraces of this error, keeping its
+            /// kind. The context trace and the global contexts are kept.
+            pub fn reset_cause_and_backtrace(&mut self) {
+                self.1.reset_cause_and_backtrace();
+            }
+
+            /// Removes the causes that are not errors of this family from the
+            /// chain, keeping the errors of this family in order.
+            ///
+            /// Errors created from a `foreign_links` type are errors of this
+            /// family and are kept. The causes of a removed foreign error are
+            /// only reachable by reference, so they are removed along with
+            /// it, even if some of them belong to this family.
+            pub fn retain_family(self) -> $error_name {
+                self.map_cause(|cause| {
+                    cause.and_then(|cause| match cause.downcast::<$error_name>() {
+                        Ok(cause) => {
+                            let cause: Box<dyn (::std::error::Error) + Send> =
+                                Box::new(cause.retain_family());
+                            Some(cause)
+                        }
+                        Err(_) => None,
+                    })
+                })
+            }
         }
 
         impl ::std::error::Error for $error_name {
@@ -174,9 +477,10 @@ macro_rules! error_chain_processed {
                 self.0.description()
             }
 
-            fn cause(&self) -> Option<&::std::error::Error> {
+            #[allow(unknown_lints, unused_doc_comments, deprecated)]
+            fn cause(&self) -> Option<&dyn (::std::error::Error)> {
                 match self.1.next_error {
-                    Some(ref c) => Some(&**c),
+                    Some(ref c) => Some($crate::unwrap_shared_cause(&**c)),
                     None => {
                         match self.0 {
                             $(
@@ -190,11 +494,58 @@ macro_rules! error_chain_processed {
                     }
                 }
             }
+
+            #[allow(unknown_lints, unused_doc_comments)]
+            fn source(&self) -> Option<&(dyn (::std::error::Error) + 'static)> {
+                match self.1.next_error {
+                    Some(ref c) => Some($crate::unwrap_shared_cause(&**c)),
+                    None => {
+                        match self.0 {
+                            $(
+                                $(#[$meta_foreign_links])*
+                                $error_kind_name::$foreign_link_variant(ref foreign_err) => {
+                                    foreign_err.source()
+                                }
+                            ) *
+                            _ => None
+                        }
+                    }
+                }
+            }
         }
 
         impl ::std::fmt::Display for $error_name {
             fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-                ::std::fmt::Display::fmt(&self.0, f)
+                self.0.fmt_with_cause(f, ::std::error::Error::source(self))?;
+                if <$error_kind_name as $crate::ErrorChainOptions>::DISPLAY_FULL_CHAIN {
+                    // The causes of this family are displayed by their kind,
+                    // so that their own causes are not repeated.
+                    let mut cause = ::std::error::Error::source(self);
+                    while let Some(e) = cause {
+                        match e.downcast_ref::<$error_name>() {
+                            Some(e) => {
+                                f.write_str(": ")?;
+                                e.0.fmt_with_cause(f, ::std::error::Error::source(e))?;
+                            }
+                            None => write!(f, ": {}", e)?,
+                        }
+                        cause = e.source();
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        impl ::std::fmt::Debug for $error_name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                if <$error_kind_name as $crate::ErrorChainOptions>::DEBUG_AS_CHAIN {
+                    ::std::fmt::Display::fmt(&$crate::ChainedError::display(self), f)
+                } else {
+                    f.debug_tuple(stringify!($error_name))
+                        .field(&self.0)
+                        .field(&self.1)
+                        .finish()
+                }
             }
         }
 
@@ -210,13 +561,57 @@ macro_rules! error_chain_processed {
             }
         ) *
 
+        $(
+            $(#[$meta_flat_links])*
+            impl From<$flat_link_error_path> for $error_name {
+                fn from(e: $flat_link_error_path) -> Self {
+                    $error_name(
+                        $error_kind_name::Msg(e.0.to_string()),
+                        e.1,
+                    )
+                }
+            }
+        ) *
+
+        // Checks the bounds of the foreign errors where they are declared,
+        // rather than deep in the generated code.
+        $(
+            $(#[$meta_foreign_links])*
+            const _: fn() = || {
+                fn assert_foreign_link_bounds<T: ::std::error::Error + Send + 'static>() {}
+                assert_foreign_link_bounds::<$foreign_link_error_path>();
+            };
+        ) *
+
         $(
             $(#[$meta_foreign_links])*
             impl From<$foreign_link_error_path> for $error_name {
                 fn from(e: $foreign_link_error_path) -> Self {
-                    $error_name::from_kind(
-                        $error_kind_name::$foreign_link_variant(e)
-                    )
+                    let err = $error_name(
+                        $error_kind_name::$foreign_link_variant(e),
+                        error_chain_foreign_link_state!($error_kind_name $( $foreign_link_no_backtrace )?),
+                    );
+                    $( let err = err.chain_err(|| $foreign_link_context); )?
+                    err
+                }
+            }
+        ) *
+
+        $(
+            $(#[$meta_mapped_links])*
+            impl From<$mapped_link_error_path> for $error_name {
+                fn from(e: $mapped_link_error_path) -> Self {
+                    $error_name::with_chain(e, $mapped_link_kind_name::$mapped_link_kind_variant)
+                }
+            }
+        ) *
+
+        $(
+            $(#[$meta_transparent_links])*
+            impl From<$transparent_link_error_path> for $error_name {
+                fn from(e: $transparent_link_error_path) -> Self {
+                    let kind = $error_kind_name::Msg(e.to_string());
+                    $error_name::with_chain(e, kind)
                 }
             }
         ) *
@@ -235,7 +630,7 @@ macro_rules! error_chain_processed {
 
         impl From<String> for $error_name {
             fn from(s: String) -> Self {
-                $error_name::from_kind(s.into())
+                $error_name::from_message(s)
             }
         }
 
@@ -247,13 +642,20 @@ macro_rules! error_chain_processed {
             }
         }
 
+        impl AsRef<$error_kind_name> for $error_name {
+            fn as_ref(&self) -> &$error_kind_name {
+                &self.0
+            }
+        }
+
 
         // The ErrorKind type
         // --------------
 
         quick_error! {
             /// The kind of an error.
-            #[derive(Debug)]
+            #[derive($($trait),*)]
+            $( #[allow($( $allow )*)] )*
             pub enum $error_kind_name {
 
                 /// A convenient variant for String.
@@ -267,6 +669,7 @@ macro_rules! error_chain_processed {
                     $link_variant(e: $link_kind_path) {
                         description(e.description())
                         display("{}", e)
+                        public_display("{}", e.public_display())
                     }
                 ) *
 
@@ -278,10 +681,51 @@ macro_rules! error_chain_processed {
                     }
                 ) *
 
+                $(
+                    $(#[$meta_groups])*
+                    $group_variant(e: $group_kind_name) {
+                        description(e.description())
+                        display("{}", e)
+                        public_display("{}", e.public_display())
+                    }
+                ) *
+
                 $($error_chunks)*
             }
         }
 
+        impl $crate::ErrorChainOptions for $error_kind_name {
+            error_chain_options! { $( $option )* }
+        }
+
+        error_chain_deserialize_kind! {
+            $error_kind_name
+            options [$( $option )*]
+            errors [$( $error_chunks )*]
+        }
+
+        error_chain_shared_cause! {
+            $error_name
+            options [$( $option )*]
+        }
+
+        error_chain_multi! {
+            $error_name $error_kind_name
+            options [$( $option )*]
+        }
+
+        error_chain_groups! {
+            $error_name $error_kind_name
+            derive [$($trait),*]
+            options { $( $option )* }
+            groups [
+                $(
+                    $group_variant ($group_kind_name) [$(#[$meta_groups])*]
+                        { $( $group_chunks )* }
+                ) *
+            ]
+        }
+
         $(
             $(#[$meta_links])*
             impl From<$link_kind_path> for $error_kind_name {
@@ -291,6 +735,15 @@ macro_rules! error_chain_processed {
             }
         ) *
 
+        $(
+            $(#[$meta_flat_links])*
+            impl From<$flat_link_kind_path> for $error_kind_name {
+                fn from(e: $flat_link_kind_path) -> Self {
+                    $error_kind_name::Msg(e.to_string())
+                }
+            }
+        ) *
+
         impl<'a> From<&'a str> for $error_kind_name {
             fn from(s: &'a str) -> Self {
                 $error_kind_name::Msg(s.to_string())
@@ -309,6 +762,39 @@ macro_rules! error_chain_processed {
             }
         }
 
+        #[allow(dead_code)]
+        impl $error_kind_name {
+            /// Returns the message if this is a `Msg` kind, `None` otherwise.
+            #[allow(unreachable_patterns)]
+            pub fn message(&self) -> Option<&str> {
+                match *self {
+                    $error_kind_name::Msg(ref s) => Some(s),
+                    _ => None,
+                }
+            }
+
+            /// Returns the dotted path of nested variant names. See
+            /// `ChainedError::kind_path`.
+            #[allow(unknown_lints, unused_doc_comments, unreachable_patterns)]
+            pub fn kind_path(&self) -> String {
+                match *self {
+                    $(
+                        $(#[$meta_links])*
+                        $error_kind_name::$link_variant(ref e) => {
+                            format!("{}.{}", stringify!($link_variant), e.kind_path())
+                        }
+                    ) *
+                    $(
+                        $(#[$meta_groups])*
+                        $error_kind_name::$group_variant(ref e) => {
+                            format!("{}.{}", stringify!($group_variant), e.variant_name())
+                        }
+                    ) *
+                    _ => self.variant_name().to_owned(),
+                }
+            }
+        }
+
         // The ResultExt trait defines the `chain_err` method.
 
         /// Additional methods for `Result`, for easy interaction with this crate.
@@ -320,84 +806,777 @@ macro_rules! error_chain_processed {
             fn chain_err<F, EK>(self, callback: F) -> ::std::result::Result<T, $error_name>
                 where F: FnOnce() -> EK,
                       EK: Into<$error_kind_name>;
+
+            /// Maps the value of an `Ok` with `f`, or chains the error with
+            /// the kind returned by `callback` as `chain_err` does.
+            fn map_chain<U, F, C, EK>(self, f: F, callback: C)
+                -> ::std::result::Result<U, $error_name>
+                where F: FnOnce(T) -> U,
+                      C: FnOnce() -> EK,
+                      EK: Into<$error_kind_name>;
+
+            /// Like `chain_err`, with a `Msg` formatted as `key=value`. The
+            /// pair is also kept in the new error, and returned by
+            /// `Error::context_kvs`, for structured logging.
+            fn chain_err_kv<V>(self, key: &'static str, value: V)
+                -> ::std::result::Result<T, $error_name>
+                where V: ::std::fmt::Display;
+
+            /// Like `chain_err`, with the kind built by a boxed closure, so
+            /// that factories of kinds can be stored as trait objects.
+            fn chain_err_dyn(self, make: Box<dyn FnOnce() -> $error_kind_name>)
+                -> ::std::result::Result<T, $error_name>;
+
+            /// Like `chain_err`, but the closure is given the original error,
+            /// so that the new kind can be built from its details.
+            fn with_context_kind<F, EK>(self, callback: F) -> ::std::result::Result<T, $error_name>
+                where F: FnOnce(&E) -> EK,
+                      EK: Into<$error_kind_name>;
+
+            /// Like `chain_err`, for a `Result<Result<T, E2>, E1>`: the error
+            /// of the outer `Result`, or else the error of the inner one, is
+            /// chained with the kind returned by the closure, so that both
+            /// layers are flattened into a `Result<T, Error>`.
+            fn chain_nested<F, EK>(self, callback: F)
+                -> ::std::result::Result<<T as $crate::NestedResult>::Ok, $error_name>
+                where T: $crate::NestedResult,
+                      <T as $crate::NestedResult>::Err: ::std::error::Error + Send + 'static,
+                      F: FnOnce() -> EK,
+                      EK: Into<$error_kind_name>;
+
+            /// Returns the kind of the error if the `Result` is an `Err`
+            /// holding an error of this family, without consuming it. Returns
+            /// `None` for other errors.
+            fn err_kind(&self) -> Option<&$error_kind_name>;
+
+            /// Converts the error into a plain `ErrorReport` containing the
+            /// messages of the error chain and the backtrace, if any.
+            fn into_report(self) -> ::std::result::Result<T, $crate::ErrorReport>;
+
+            /// Returns the value of an `Ok`, or panics with `msg` followed by
+            /// the whole chain of the error, as printed by
+            /// `ChainedError::display`, which is more useful than the `Debug`
+            /// output printed by `expect`.
+            fn expect_chain(self, msg: &str) -> T;
+
+            /// Converts the error into an error of this family, capturing a
+            /// backtrace here if it doesn't have one yet, as the family's
+            /// errors are when they are created. Errors of other types become
+            /// a `Msg` with their message, chained to the original error.
+            fn trace_err(self) -> ::std::result::Result<T, $error_name>;
+
+            /// Converts the error into an error of this family like
+            /// `trace_err`, but uses `backtrace` as its backtrace if it doesn't
+            /// have one yet instead of capturing a new one. The backtrace is
+            /// recorded as captured on the current thread.
+            fn with_backtrace(self, backtrace: $crate::Backtrace)
+                -> ::std::result::Result<T, $error_name>;
+
+            /// Converts the error into a `String` containing the messages of
+            /// its chain on a single line, as
+            /// `ChainedError::display_chain_inline` does. The kinds, causes
+            /// and backtrace are lost.
+            fn stringify_err(self) -> ::std::result::Result<T, String>;
+
+            /// Converts the `Result` into an `Option`, calling `log` with the
+            /// error, converted into an error of this family as by
+            /// `trace_err`, if it is an `Err`.
+            fn ok_or_log<F>(self, log: F) -> Option<T>
+                where F: FnOnce(&$error_name);
+
+            /// Converts the error into an error of this family as by
+            /// `trace_err`, then replaces it with `Ok(ok)` if `pred` returns
+            /// `true` for it.
+            fn filter_err<P>(self, pred: P, ok: T) -> ::std::result::Result<T, $error_name>
+                where P: FnOnce(&$error_name) -> bool;
         }
 
         impl<T, E> $result_ext_name<T, E> for ::std::result::Result<T, E> where E: ::std::error::Error + Send + 'static {
+            #[track_caller]
             fn chain_err<F, EK>(self, callback: F) -> ::std::result::Result<T, $error_name>
                 where F: FnOnce() -> EK,
                       EK: Into<$error_kind_name> {
+                let location = ::std::panic::Location::caller();
                 self.map_err(move |e| {
-                    let state = $crate::State::new::<$error_name>(Box::new(e), );
+                    let mut state = $crate::State::new::<$error_name>(Box::new(e), )
+                        .with_options::<$error_kind_name>();
+                    state.push_context(location.file(), location.line());
                     $crate::ChainedError::new(callback().into(), state)
                 })
             }
-        }
 
+            #[track_caller]
+            fn map_chain<U, F, C, EK>(self, f: F, callback: C)
+                -> ::std::result::Result<U, $error_name>
+                where F: FnOnce(T) -> U,
+                      C: FnOnce() -> EK,
+                      EK: Into<$error_kind_name> {
+                $result_ext_name::chain_err(self, callback).map(f)
+            }
 
-    };
-}
+            #[track_caller]
+            fn chain_err_kv<V>(self, key: &'static str, value: V)
+                -> ::std::result::Result<T, $error_name>
+                where V: ::std::fmt::Display {
+                let location = ::std::panic::Location::caller();
+                self.map_err(move |e| {
+                    let value = value.to_string();
+                    let kind = format!("{}={}", key, value).into();
+                    let mut state = $crate::State::new::<$error_name>(Box::new(e), )
+                        .with_options::<$error_kind_name>();
+                    state.push_context(location.file(), location.line());
+                    state.extras_mut().context_kv = Some((key, value));
+                    $crate::ChainedError::new(kind, state)
+                })
+            }
 
-/// Internal macro used for reordering of the fields.
-#[doc(hidden)]
-#[macro_export]
-macro_rules! error_chain_processing {
-    (
-        ({}, $b:tt, $c:tt, $d:tt, $e:tt)
-        types $content:tt
-        $( $tail:tt )*
-    ) => {
-        error_chain_processing! {
-            ($content, $b, $c, $d, $e)
-            $($tail)*
-        }
-    };
-    (
-        ($a:tt, {}, $c:tt, $d:tt, $e:tt)
-        derive $content:tt
-        $( $tail:tt )*
-    ) => {
-        error_chain_processing! {
-            ($a, $content, $c, $d, $e)
-            $($tail)*
-        }
-    };
-    (
-        ($a:tt, $b:tt, {}, $d:tt, $e:tt)
-        links $content:tt
-        $( $tail:tt )*
-    ) => {
-        error_chain_processing! {
-            ($a, $b, $content, $d, $e)
+            #[track_caller]
+            fn chain_err_dyn(self, make: Box<dyn FnOnce() -> $error_kind_name>)
+                -> ::std::result::Result<T, $error_name> {
+                $result_ext_name::chain_err(self, make)
+            }
+
+            #[track_caller]
+            fn with_context_kind<F, EK>(self, callback: F) -> ::std::result::Result<T, $error_name>
+                where F: FnOnce(&E) -> EK,
+                      EK: Into<$error_kind_name> {
+                let location = ::std::panic::Location::caller();
+                self.map_err(move |e| {
+                    let kind = callback(&e).into();
+                    let mut state = $crate::State::new::<$error_name>(Box::new(e), )
+                        .with_options::<$error_kind_name>();
+                    state.push_context(location.file(), location.line());
+                    $crate::ChainedError::new(kind, state)
+                })
+            }
+
+            #[track_caller]
+            fn chain_nested<F, EK>(self, callback: F)
+                -> ::std::result::Result<<T as $crate::NestedResult>::Ok, $error_name>
+                where T: $crate::NestedResult,
+                      <T as $crate::NestedResult>::Err: ::std::error::Error + Send + 'static,
+                      F: FnOnce() -> EK,
+                      EK: Into<$error_kind_name> {
+                match self {
+                    Ok(inner) => {
+                        $result_ext_name::chain_err($crate::NestedResult::into_result(inner), callback)
+                    }
+                    Err(e) => $result_ext_name::chain_err(Err(e), callback),
+                }
+            }
+
+            fn err_kind(&self) -> Option<&$error_kind_name> {
+                match *self {
+                    Err(ref e) => {
+                        let e: &dyn (::std::any::Any) = e;
+                        e.downcast_ref::<$error_name>().map(|e| e.kind())
+                    }
+                    Ok(_) => None,
+                }
+            }
+
+            fn into_report(self) -> ::std::result::Result<T, $crate::ErrorReport> {
+                self.map_err(|e| $crate::ErrorReport::new::<$error_name>(&e))
+            }
+
+            #[track_caller]
+            fn expect_chain(self, msg: &str) -> T {
+                match self {
+                    Ok(v) => v,
+                    Err(e) => $crate::expect_chain_failed::<$error_name>(msg, Box::new(e)),
+                }
+            }
+
+            fn trace_err(self) -> ::std::result::Result<T, $error_name> {
+                self.map_err(|e| {
+                    let e: Box<dyn (::std::error::Error) + Send> = Box::new(e);
+                    match e.downcast::<$error_name>() {
+                        Ok(e) => {
+                            let $error_name(kind, mut state) = *e;
+                            state.ensure_backtrace();
+                            $error_name(kind, state.with_options::<$error_kind_name>())
+                        }
+                        Err(e) => {
+                            let kind = $error_kind_name::Msg(e.to_string());
+                            $error_name(kind, $crate::State::new::<$error_name>(e)
+                                .with_options::<$error_kind_name>())
+                        }
+                    }
+                })
+            }
+
+            fn with_backtrace(self, backtrace: $crate::Backtrace)
+                -> ::std::result::Result<T, $error_name> {
+                self.map_err(|e| {
+                    let e: Box<dyn (::std::error::Error) + Send> = Box::new(e);
+                    match e.downcast::<$error_name>() {
+                        Ok(mut e) => {
+                            e.1.attach_backtrace(backtrace);
+                            *e
+                        }
+                        Err(e) => {
+                            let kind = $error_kind_name::Msg(e.to_string());
+                            $error_name(kind, $crate::State::from_parts(Some(e), Some(backtrace))
+                                .with_options::<$error_kind_name>())
+                        }
+                    }
+                })
+            }
+
+            fn stringify_err(self) -> ::std::result::Result<T, String> {
+                self.map_err(|e| $crate::display_inline(&e).to_string())
+            }
+
+            fn ok_or_log<F>(self, log: F) -> Option<T>
+                where F: FnOnce(&$error_name) {
+                match $result_ext_name::trace_err(self) {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        log(&e);
+                        None
+                    }
+                }
+            }
+
+            fn filter_err<P>(self, pred: P, ok: T) -> ::std::result::Result<T, $error_name>
+                where P: FnOnce(&$error_name) -> bool {
+                match $result_ext_name::trace_err(self) {
+                    Err(ref e) if pred(e) => Ok(ok),
+                    res => res,
+                }
+            }
+        }
+
+
+    };
+}
+
+/// Internal macro used for reordering of the fields.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_processing {
+    (
+        ({}, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt)
+        types $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($content, $b, $c, $d, $e, $f, $g, $h, $i)
+            $($tail)*
+        }
+    };
+    (
+        ($a:tt, {}, $c:tt, $d:tt, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt)
+        derive $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $content, $c, $d, $e, $f, $g, $h, $i)
+            $($tail)*
+        }
+    };
+    (
+        ($a:tt, $b:tt, {}, $d:tt, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt)
+        links $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $b, $content, $d, $e, $f, $g, $h, $i)
             $($tail)*
         }
     };
     (
-        ($a:tt, $b:tt, $c:tt, {}, $e:tt)
+        ($a:tt, $b:tt, $c:tt, {}, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt)
         foreign_links $content:tt
         $( $tail:tt )*
     ) => {
         error_chain_processing! {
-            ($a, $b, $c, $content, $e)
+            ($a, $b, $c, $content, $e, $f, $g, $h, $i)
             $($tail)*
         }
     };
     (
-        ($a:tt, $b:tt, $c:tt, $d:tt, {})
+        ($a:tt, $b:tt, $c:tt, $d:tt, {}, $f:tt, $g:tt, $h:tt, $i:tt)
+        transparent_foreign_links $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $b, $c, $d, $content, $f, $g, $h, $i)
+            $($tail)*
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, {}, $g:tt, $h:tt, $i:tt)
         errors $content:tt
         $( $tail:tt )*
     ) => {
         error_chain_processing! {
-            ($a, $b, $c, $d, $content)
+            ($a, $b, $c, $d, $e, $content, $g, $h, $i)
+            $($tail)*
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, {}, $h:tt, $i:tt)
+        options $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $b, $c, $d, $e, $f, $content, $h, $i)
             $($tail)*
         }
     };
-    ( ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt) ) => {
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $g:tt, {}, $i:tt)
+        groups $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $b, $c, $d, $e, $f, $g, $content, $i)
+            $($tail)*
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $g:tt, $h:tt, {})
+        flat_links $content:tt
+        $( $tail:tt )*
+    ) => {
+        error_chain_processing! {
+            ($a, $b, $c, $d, $e, $f, $g, $h, $content)
+            $($tail)*
+        }
+    };
+    ( ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt) ) => {
+        error_chain_allow! {
+            ($a, $b, $c, $d, $e, $f, $h, $i)
+            [] []
+            $g
+        }
+    };
+}
+
+/// Internal macro separating the `allow(...)` options from the other ones.
+/// It also adds the `Multiple` variant to the `errors` if the `multi`
+/// option is set.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_allow {
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $h:tt, $i:tt)
+        $options:tt $allow:tt
+        { multi; $( $tail:tt )* }
+    ) => {
+        error_chain_allow! {
+            ($a, $b, $c, $d, $e, $f, $h, $i)
+            multi $a
+            $options $allow
+            { $( $tail )* }
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, { $( $f:tt )* }, $h:tt, $i:tt)
+        multi { $( $error_name:ident, $( $rest:ident ),* ; )? }
+        [$( $options:tt )*] $allow:tt
+        $tail:tt
+    ) => {
+        error_chain_allow! {
+            ($a, $b, $c, $d, $e, {
+                $( $f )*
+
+                /// Several independent errors.
+                Multiple(errors: Vec<error_chain_allow!(multi_name $( $error_name )?)>) {
+                    description("multiple errors")
+                    display("{}", $crate::display_multiple(errors))
+                }
+            }, $h, $i)
+            [$( $options )* multi;] $allow
+            $tail
+        }
+    };
+    (multi_name) => { Error };
+    (multi_name $error_name:ident) => { $error_name };
+    (
+        $blocks:tt
+        multi $types:tt
+        $( $rest:tt )*
+    ) => {
+        compile_error!("the `multi` option can't be used by an error family declared \
+                        with a lifetime");
+    };
+    (
+        $blocks:tt
+        [$( $options:tt )*] [$( $allow:tt )*]
+        { allow( $( $lints:tt )* ); $( $tail:tt )* }
+    ) => {
+        error_chain_allow! {
+            $blocks
+            [$( $options )*]
+            [$( $allow )* ($( $lints )*)]
+            { $( $tail )* }
+        }
+    };
+    (
+        $blocks:tt
+        [$( $options:tt )*] [$( $allow:tt )*]
+        { $t:tt $( $tail:tt )* }
+    ) => {
+        error_chain_allow! {
+            $blocks
+            [$( $options )* $t]
+            [$( $allow )*]
+            { $( $tail )* }
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $d:tt, $e:tt, $f:tt, $h:tt, $i:tt)
+        [$( $options:tt )*] [$( $allow:tt )*]
+        {}
+    ) => {
+        error_chain_foreign_links! {
+            ($a, $b, $c, $e, $f, { $( $options )* }, $h, $i, { $( $allow )* })
+            [] []
+            $d
+        }
+    };
+}
+
+/// Internal macro separating the foreign links mapped to an existing kind
+/// with `as` from the other ones.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_foreign_links {
+    (
+        $blocks:tt
+        [$( $foreign_links:tt )*] [$( $mapped_foreign_links:tt )*]
+        {
+            $variant:ident ( $error_path:path ) as $kind_name:ident :: $kind_variant:ident
+               $( #[$meta:meta] )*;
+            $( $tail:tt )*
+        }
+    ) => {
+        error_chain_foreign_links! {
+            $blocks
+            [$( $foreign_links )*]
+            [$( $mapped_foreign_links )* $variant($error_path) as $kind_name::$kind_variant $( #[$meta] )*;]
+            { $( $tail )* }
+        }
+    };
+    (
+        $blocks:tt
+        [$( $foreign_links:tt )*] [$( $mapped_foreign_links:tt )*]
+        {
+            $variant:ident ( $error_path:path ) no_backtrace
+               $( #[$meta:meta] )*;
+            $( $tail:tt )*
+        }
+    ) => {
+        error_chain_foreign_links! {
+            $blocks
+            [$( $foreign_links )* $variant($error_path) [no_backtrace] $( #[$meta] )*;]
+            [$( $mapped_foreign_links )*]
+            { $( $tail )* }
+        }
+    };
+    (
+        $blocks:tt
+        [$( $foreign_links:tt )*] [$( $mapped_foreign_links:tt )*]
+        {
+            $variant:ident ( $error_path:path ) $( context($context:expr) )?
+               $( #[$meta:meta] )*;
+            $( $tail:tt )*
+        }
+    ) => {
+        error_chain_foreign_links! {
+            $blocks
+            [$( $foreign_links )* $variant($error_path) $( context($context) )? [] $( #[$meta] )*;]
+            [$( $mapped_foreign_links )*]
+            { $( $tail )* }
+        }
+    };
+    (
+        ($a:tt, $b:tt, $c:tt, $e:tt, $f:tt, $g:tt, $h:tt, $i:tt, $j:tt)
+        [$( $foreign_links:tt )*] [$( $mapped_foreign_links:tt )*]
+        {}
+    ) => {
         error_chain_processed! {
             types $a
-            derive $e
-            links $b
-            foreign_links $c
-            errors $d
+            derive $b
+            allow $j
+            links $c
+            flat_links $i
+            foreign_links { $( $foreign_links )* }
+            mapped_foreign_links { $( $mapped_foreign_links )* }
+            transparent_foreign_links $e
+            errors $f
+            options $g
+            groups $h
+        }
+    };
+}
+
+/// Internal macro defining an error family declared with a lifetime. Such
+/// errors are not `'static`, so they can't be boxed as causes and only
+/// contain their kind.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_borrowed {
+    // Default values for `derive`.
+    (
+        types $types:tt
+        derive {}
+        $( $rest: tt )*
+    ) => {
+        error_chain_borrowed! {
+            types $types
+            derive {
+                Debug;
+            }
+            $( $rest )*
+        }
+    };
+    (
+        types {
+            $error_name:ident < $lt:lifetime >, $error_kind_name:ident < $kind_lt:lifetime >,
+            $result_ext_name:ident $( , $result_name:ident )?;
+        }
+
+        derive {
+            $($trait:ident),*;
+        }
+
+        allow {
+            $( ( $( $allow:tt )* ) )*
+        }
+
+        links {}
+
+        flat_links {}
+
+        foreign_links {}
+
+        mapped_foreign_links {}
+
+        transparent_foreign_links {}
+
+        errors {
+            $( $error_chunks:tt ) *
+        }
+
+        options {
+            $( $option:tt )*
+        }
+
+        groups {}
+
+    ) => {
+        error_chain_borrowed_struct! {
+            $error_name < $lt > $error_kind_name
+            allow { $( ( $( $allow )* ) )* }
+            options [$( $option )*]
+        }
+
+        #[allow(dead_code)]
+        impl < $lt > $error_name < $lt > {
+            /// Constructs an error from a message, as the kind chosen by the
+            /// `bail_kind(...)` option, or `Msg` by default. This is how
+            /// `String`s are converted into the error, as by `bail!` with a
+            /// formatted string.
+            pub fn from_message(message: String) -> Self {
+                $error_name::from_kind($crate::ErrorChainOptions::bail_kind(message))
+            }
+
+            /// Returns the kind of the error.
+            pub fn kind(&self) -> &$error_kind_name < $lt > {
+                &self.0
+            }
+        }
+
+        impl < $lt > ::std::error::Error for $error_name < $lt > {
+            fn description(&self) -> &str {
+                self.0.description()
+            }
+        }
+
+        impl < $lt > ::std::fmt::Display for $error_name < $lt > {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                ::std::fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl < $lt > From<$error_kind_name < $lt >> for $error_name < $lt > {
+            fn from(e: $error_kind_name < $lt >) -> Self {
+                $error_name::from_kind(e)
+            }
+        }
+
+        impl < $lt > From<& $lt str> for $error_name < $lt > {
+            fn from(s: & $lt str) -> Self {
+                $error_name::from_kind(s.into())
+            }
+        }
+
+        impl < $lt > From<String> for $error_name < $lt > {
+            fn from(s: String) -> Self {
+                $error_name::from_message(s)
+            }
+        }
+
+        impl < $lt > ::std::ops::Deref for $error_name < $lt > {
+            type Target = $error_kind_name < $lt >;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl < $lt > AsRef<$error_kind_name < $lt >> for $error_name < $lt > {
+            fn as_ref(&self) -> &$error_kind_name < $lt > {
+                &self.0
+            }
+        }
+
+        $(
+            /// Convenient wrapper around `std::Result`.
+            #[allow(unused)]
+            pub type $result_name < $lt, T > = ::std::result::Result<T, $error_name < $lt >>;
+        )?
+
+        quick_error! {
+            /// The kind of an error.
+            #[derive($($trait),*)]
+            $( #[allow($( $allow )*)] )*
+            pub enum $error_kind_name < $lt > {
+
+                /// A convenient variant for String.
+                Msg(s: String) {
+                    description(&s)
+                    display("{}", s)
+                }
+
+                $($error_chunks)*
+            }
+        }
+
+        impl < $lt > $crate::ErrorChainOptions for $error_kind_name < $lt > {
+            error_chain_options! { $( $option )* }
+        }
+
+        impl < $lt > From<& $lt str> for $error_kind_name < $lt > {
+            fn from(s: & $lt str) -> Self {
+                $error_kind_name::Msg(s.to_string())
+            }
+        }
+
+        impl < $lt > From<String> for $error_kind_name < $lt > {
+            fn from(s: String) -> Self {
+                $error_kind_name::Msg(s)
+            }
+        }
+
+        impl < $lt > From<$error_name < $lt >> for $error_kind_name < $lt > {
+            fn from(e: $error_name < $lt >) -> Self {
+                e.0
+            }
+        }
+
+        #[allow(dead_code)]
+        impl < $lt > $error_kind_name < $lt > {
+            /// Returns the message if this is a `Msg` kind, `None` otherwise.
+            #[allow(unreachable_patterns)]
+            pub fn message(&self) -> Option<&str> {
+                match *self {
+                    $error_kind_name::Msg(ref s) => Some(s),
+                    _ => None,
+                }
+            }
+        }
+    };
+    (
+        types $types:tt
+        $( $rest:tt )*
+    ) => {
+        compile_error!("an error family declared with a lifetime can only have the \
+                        `types`, `derive`, `errors` and `options` blocks");
+    };
+}
+
+/// Internal macro defining the `Error` of a family declared with a lifetime,
+/// which also has a borrowed cause if the `scoped` option is set.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_borrowed_struct {
+    (
+        $error_name:ident < $lt:lifetime > $error_kind_name:ident
+        allow { $( ( $( $allow:tt )* ) )* }
+        options [scoped; $( $option:tt )*]
+    ) => {
+        /// The Error type.
+        ///
+        /// It borrows from the data of its kind and from its cause, so unlike
+        /// the errors of families declared without a lifetime it has no
+        /// backtrace, and its cause is not returned by `source`.
+        #[derive(Debug)]
+        $( #[allow($( $allow )*)] )*
+        pub struct $error_name < $lt > (
+            /// The kind of the error.
+            pub $error_kind_name < $lt >,
+            /// The cause of the error.
+            pub Option<& $lt (dyn (::std::error::Error) + $lt)>,
+        );
+
+        #[allow(dead_code)]
+        impl < $lt > $error_name < $lt > {
+            /// Constructs an error from a kind.
+            pub fn from_kind(kind: $error_kind_name < $lt >) -> Self {
+                $error_name(kind, None)
+            }
+
+            /// Constructs an error from a kind and a borrowed cause.
+            pub fn with_chain<K>(cause: & $lt (dyn (::std::error::Error) + $lt), kind: K) -> Self
+                where K: Into<$error_kind_name < $lt >>
+            {
+                $error_name(kind.into(), Some(cause))
+            }
+
+            /// Returns the cause of the error.
+            pub fn scoped_cause(&self) -> Option<& $lt (dyn (::std::error::Error) + $lt)> {
+                self.1
+            }
+        }
+    };
+    (
+        $error_name:ident < $lt:lifetime > $error_kind_name:ident
+        allow $allow:tt
+        options [$t:tt $( $option:tt )*]
+    ) => {
+        error_chain_borrowed_struct! {
+            $error_name < $lt > $error_kind_name
+            allow $allow
+            options [$( $option )*]
+        }
+    };
+    (
+        $error_name:ident < $lt:lifetime > $error_kind_name:ident
+        allow { $( ( $( $allow:tt )* ) )* }
+        options []
+    ) => {
+        /// The Error type.
+        ///
+        /// It borrows from the data of its kind, so unlike the errors of
+        /// families declared without a lifetime it has no cause and no
+        /// backtrace.
+        #[derive(Debug)]
+        $( #[allow($( $allow )*)] )*
+        pub struct $error_name < $lt > (
+            /// The kind of the error.
+            pub $error_kind_name < $lt >,
+        );
+
+        #[allow(dead_code)]
+        impl < $lt > $error_name < $lt > {
+            /// Constructs an error from a kind.
+            pub fn from_kind(kind: $error_kind_name < $lt >) -> Self {
+                $error_name(kind)
+            }
         }
     };
 }
@@ -406,14 +1585,320 @@ macro_rules! error_chain_processing {
 /// the exact rules, see `error_chain_processed`.
 #[macro_export]
 macro_rules! error_chain {
+    ( cfg( $( $cfg:tt )* ) $( $rest:tt )* ) => {
+        #[cfg( $( $cfg )* )]
+        error_chain! { $( $rest )* }
+    };
     ( $( $block_name:ident { $( $block_content:tt )* } )* ) => {
         error_chain_processing! {
-            ({}, {}, {}, {}, {})
+            ({}, {}, {}, {}, {}, {}, {}, {}, {})
             $($block_name { $( $block_content )* })*
         }
     };
 }
 
+/// Internal macro defining the enums of the `groups` block, one group at a
+/// time.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_groups {
+    (
+        $error_name:ident $error_kind_name:ident
+        derive [$($trait:ident),*]
+        options { $( $option:tt )* }
+        groups []
+    ) => {};
+    (
+        $error_name:ident $error_kind_name:ident
+        derive [$($trait:ident),*]
+        options { $( $option:tt )* }
+        groups [
+            $group_variant:ident ($group_kind_name:ident) [$(#[$meta_groups:meta])*]
+                { $( $group_chunks:tt )* }
+            $( $tail:tt )*
+        ]
+    ) => {
+        quick_error! {
+            /// A group of kinds of errors, wrapped by a variant of the same
+            /// name in the kind of the error family.
+            $(#[$meta_groups])*
+            #[derive($($trait),*)]
+            pub enum $group_kind_name {
+                $( $group_chunks )*
+            }
+        }
+
+        $(#[$meta_groups])*
+        impl $crate::ErrorChainOptions for $group_kind_name {
+            error_chain_options! { group $( $option )* }
+        }
+
+        $(#[$meta_groups])*
+        impl From<$group_kind_name> for $error_kind_name {
+            fn from(e: $group_kind_name) -> Self {
+                $error_kind_name::$group_variant(e)
+            }
+        }
+
+        $(#[$meta_groups])*
+        impl From<$group_kind_name> for $error_name {
+            fn from(e: $group_kind_name) -> Self {
+                $error_name::from_kind(e.into())
+            }
+        }
+
+        error_chain_groups! {
+            $error_name $error_kind_name
+            derive [$($trait),*]
+            options { $( $option )* }
+            groups [ $( $tail )* ]
+        }
+    };
+}
+
+/// Internal macro building the `State` of the errors converted from a
+/// foreign link, without a backtrace if it is declared `no_backtrace`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_foreign_link_state {
+    ($error_kind_name:ident) => {
+        $crate::State::default().with_options::<$error_kind_name>()
+    };
+    ($error_kind_name:ident no_backtrace) => {
+        $crate::State::from_parts(None, None)
+    };
+}
+
+/// Internal macro turning the `options` block into the items of the
+/// `ErrorChainOptions` implementation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_options {
+    () => {};
+    // The kinds of the groups don't have the variant of `bail_kind`.
+    (group) => {};
+    (group bail_kind($variant:ident); $( $tail:tt )*) => {
+        error_chain_options! { group $( $tail )* }
+    };
+    (group $option:ident; $( $tail:tt )*) => {
+        error_chain_options! { $option; }
+        error_chain_options! { group $( $tail )* }
+    };
+    (display_with_code; $( $tail:tt )*) => {
+        const DISPLAY_WITH_CODE: bool = true;
+        error_chain_options! { $( $tail )* }
+    };
+    // Handled by `error_chain_deserialize_kind`.
+    (deserialize_kind; $( $tail:tt )*) => {
+        error_chain_options! { $( $tail )* }
+    };
+    (catch_all_foreign; $( $tail:tt )*) => {
+        compile_error!("the `catch_all_foreign` option can't be supported, as a blanket \
+                        `From` impl would conflict with `impl<T> From<T> for T`; declare the \
+                        errors in `transparent_foreign_links` instead");
+    };
+    // Handled by `error_chain_shared_cause`.
+    (shared_cause; $( $tail:tt )*) => {
+        error_chain_options! { $( $tail )* }
+    };
+    (display_full_chain; $( $tail:tt )*) => {
+        const DISPLAY_FULL_CHAIN: bool = true;
+        error_chain_options! { $( $tail )* }
+    };
+    (always_backtrace; $( $tail:tt )*) => {
+        const ALWAYS_BACKTRACE: bool = true;
+        error_chain_options! { $( $tail )* }
+    };
+    // Handled by `error_chain_allow` and `error_chain_multi`.
+    (multi; $( $tail:tt )*) => {
+        error_chain_options! { $( $tail )* }
+    };
+    // Handled by `error_chain_borrowed_struct`.
+    (scoped; $( $tail:tt )*) => {
+        error_chain_options! { $( $tail )* }
+    };
+    (debug_as_chain; $( $tail:tt )*) => {
+        const DEBUG_AS_CHAIN: bool = true;
+        error_chain_options! { $( $tail )* }
+    };
+    (bail_kind($variant:ident); $( $tail:tt )*) => {
+        fn bail_kind(message: String) -> Self {
+            Self::$variant(message)
+        }
+        error_chain_options! { $( $tail )* }
+    };
+}
+
+/// Internal macro implementing `Deserialize` for the `ErrorKind` if the
+/// `deserialize_kind` option is set.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "serde")]
+macro_rules! error_chain_deserialize_kind {
+    (
+        $error_kind_name:ident
+        options [deserialize_kind; $( $option:tt )*]
+        errors [$( $(#[$meta:meta])* $variant:ident $( { $( $funcs:tt )* } )? )*]
+    ) => {
+        impl<'de> $crate::serde::Deserialize<'de> for $error_kind_name {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+                where D: $crate::serde::Deserializer<'de>
+            {
+                use $crate::serde::de::{self, Error, MapAccess, Unexpected};
+
+                struct KindVisitor;
+
+                impl<'de> de::Visitor<'de> for KindVisitor {
+                    type Value = $error_kind_name;
+
+                    fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        f.write_str(concat!("the name of a unit variant of `",
+                                            stringify!($error_kind_name),
+                                            "` or a `Msg`"))
+                    }
+
+                    #[allow(unknown_lints, unused_doc_comments)]
+                    fn visit_str<E: Error>(self, v: &str) -> ::std::result::Result<Self::Value, E> {
+                        match v {
+                            $(
+                                $(#[$meta])*
+                                stringify!($variant) => Ok($error_kind_name::$variant),
+                            )*
+                            _ => Err(E::invalid_value(Unexpected::Str(v), &self)),
+                        }
+                    }
+
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A)
+                        -> ::std::result::Result<Self::Value, A::Error>
+                    {
+                        match map.next_key::<String>()? {
+                            Some(ref key) if key == "Msg" => Ok($error_kind_name::Msg(map.next_value()?)),
+                            Some(key) => Err(A::Error::invalid_value(Unexpected::Str(&key), &self)),
+                            None => Err(A::Error::invalid_length(0, &self)),
+                        }
+                    }
+                }
+
+                deserializer.deserialize_any(KindVisitor)
+            }
+        }
+    };
+    (
+        $error_kind_name:ident
+        options [deserialize_kind; $( $option:tt )*]
+        errors [$( $error_chunks:tt )*]
+    ) => {
+        compile_error!("the `deserialize_kind` option only supports `errors` variants without fields");
+    };
+    (
+        $error_kind_name:ident
+        options [$t:tt $( $option:tt )*]
+        errors $errors:tt
+    ) => {
+        error_chain_deserialize_kind! {
+            $error_kind_name
+            options [$( $option )*]
+            errors $errors
+        }
+    };
+    (
+        $error_kind_name:ident
+        options []
+        errors $errors:tt
+    ) => {};
+}
+
+/// Internal macro implementing `Deserialize` for the `ErrorKind` if the
+/// `deserialize_kind` option is set.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "serde"))]
+macro_rules! error_chain_deserialize_kind {
+    (
+        $error_kind_name:ident
+        options [deserialize_kind; $( $option:tt )*]
+        errors $errors:tt
+    ) => {
+        compile_error!("the `deserialize_kind` option requires the `serde` feature");
+    };
+    (
+        $error_kind_name:ident
+        options [$t:tt $( $option:tt )*]
+        errors $errors:tt
+    ) => {
+        error_chain_deserialize_kind! {
+            $error_kind_name
+            options [$( $option )*]
+            errors $errors
+        }
+    };
+    (
+        $error_kind_name:ident
+        options []
+        errors $errors:tt
+    ) => {};
+}
+
+/// Internal macro implementing `From<Vec<Error>>` for the `Error` if the
+/// `multi` option is set.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_multi {
+    (
+        $error_name:ident $error_kind_name:ident
+        options [multi; $( $option:tt )*]
+    ) => {
+        impl From<Vec<$error_name>> for $error_name {
+            fn from(errors: Vec<$error_name>) -> Self {
+                $error_name::from_kind($error_kind_name::Multiple(errors))
+            }
+        }
+    };
+    (
+        $error_name:ident $error_kind_name:ident
+        options [$t:tt $( $option:tt )*]
+    ) => {
+        error_chain_multi! {
+            $error_name $error_kind_name
+            options [$( $option )*]
+        }
+    };
+    (
+        $error_name:ident $error_kind_name:ident
+        options []
+    ) => {};
+}
+
+/// Internal macro implementing `Clone` for the `Error` if the
+/// `shared_cause` option is set.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! error_chain_shared_cause {
+    (
+        $error_name:ident
+        options [shared_cause; $( $option:tt )*]
+    ) => {
+        impl Clone for $error_name {
+            fn clone(&self) -> Self {
+                $error_name(self.0.clone(), self.1.clone_shared::<$error_name>())
+            }
+        }
+    };
+    (
+        $error_name:ident
+        options [$t:tt $( $option:tt )*]
+    ) => {
+        error_chain_shared_cause! {
+            $error_name
+            options [$( $option )*]
+        }
+    };
+    (
+        $error_name:ident
+        options []
+    ) => {};
+}
+
 /// Macro used to manage the `backtrace` feature.
 ///
 /// See
@@ -426,8 +1911,9 @@ macro_rules! impl_extract_backtrace {
     ($error_name: ident
      $error_kind_name: ident
      $([$link_error_path: path, $(#[$meta_links: meta])*])*) => {
-        fn extract_backtrace(e: &(::std::error::Error + Send + 'static))
-            -> Option<::std::sync::Arc<$crate::Backtrace>> {
+        #[allow(unknown_lints, unused_doc_comments)]
+        fn extract_backtrace(e: &(dyn (::std::error::Error) + 'static))
+            -> Option<::std::sync::Arc<$crate::InternalBacktrace>> {
             if let Some(e) = e.downcast_ref::<$error_name>() {
                 return e.1.backtrace.clone();
             }
@@ -441,6 +1927,23 @@ macro_rules! impl_extract_backtrace {
             ) *
             None
         }
+
+        #[allow(unknown_lints, unused_doc_comments)]
+        fn extract_later_backtraces(e: &(dyn (::std::error::Error) + 'static))
+            -> Vec<::std::sync::Arc<$crate::InternalBacktrace>> {
+            if let Some(e) = e.downcast_ref::<$error_name>() {
+                return e.1.later_backtraces.clone();
+            }
+            $(
+                $( #[$meta_links] )*
+                {
+                    if let Some(e) = e.downcast_ref::<$link_error_path>() {
+                        return e.1.later_backtraces.clone();
+                    }
+                }
+            ) *
+            Vec::new()
+        }
     }
 }
 
@@ -457,3 +1960,38 @@ macro_rules! impl_extract_backtrace {
      $error_kind_name: ident
      $([$link_error_path: path, $(#[$meta_links: meta])*])*) => {}
 }
+
+/// Macro used to manage the `context-trace` feature.
+#[macro_export]
+#[doc(hidden)]
+#[cfg(feature = "context-trace")]
+macro_rules! impl_extract_context_trace {
+    ($error_name: ident
+     $([$link_error_path: path, $(#[$meta_links: meta])*])*) => {
+        #[allow(unknown_lints, unused_doc_comments)]
+        fn extract_context_trace(e: &(dyn (::std::error::Error) + 'static))
+            -> Vec<(&'static str, u32)> {
+            if let Some(e) = e.downcast_ref::<$error_name>() {
+                return e.1.context_trace.to_vec();
+            }
+            $(
+                $( #[$meta_links] )*
+                {
+                    if let Some(e) = e.downcast_ref::<$link_error_path>() {
+                        return e.1.context_trace.to_vec();
+                    }
+                }
+            ) *
+            Vec::new()
+        }
+    }
+}
+
+/// Macro used to manage the `context-trace` feature.
+#[macro_export]
+#[doc(hidden)]
+#[cfg(not(feature = "context-trace"))]
+macro_rules! impl_extract_context_trace {
+    ($error_name: ident
+     $([$link_error_path: path, $(#[$meta_links: meta])*])*) => {}
+}
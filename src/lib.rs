@@ -1,6 +1,7 @@
 #![deny(missing_docs)]
-// TODO: remove
-#![feature(log_syntax)]
+// `std::error::Report` is still unstable, so `ChainedError::to_report` is
+// only available with `--cfg error_chain_nightly` on a nightly compiler.
+#![cfg_attr(error_chain_nightly, feature(error_reporter))]
 
 //! A library for consistent and reliable error handling
 //!
@@ -47,7 +48,7 @@
 //! * Conversions between error types are done in an automatic and
 //!   consistent way - `From` conversion behavior is never specified
 //!   explicitly.
-//! * Errors implement Send.
+//! * Errors implement Send. This is enforced by the test suite.
 //! * Errors can carry backtraces.
 //!
 //! Similar to other libraries like [error-type] and [quick-error],
@@ -122,6 +123,15 @@
 //!     //     Error, ErrorKind, ResultExt;
 //!     // }
 //!
+//!     // The traits derived for `ErrorKind`. `Debug` must be one of them,
+//!     // as the `Debug` implementation of `Error` uses it.
+//!     //
+//!     // It is also possible to leave this section out entirely, or
+//!     // leave it empty, and only `Debug` will be derived.
+//!     derive {
+//!         Debug;
+//!     }
+//!
 //!     // Automatic conversions between this error chain and other
 //!     // error chains. In this case, it will e.g. generate an
 //!     // `ErrorKind` variant called `Another` which in turn contains
@@ -164,20 +174,193 @@
 //!             description("unknown toolchain version"), // note the ,
 //!             display("unknown toolchain version: '{}'", v), // trailing comma is allowed
 //!         }
+//!
+//!         // A numeric code can be attached to a variant, and is returned
+//!         // by `ErrorKind::code`. If any code is declared, `ErrorKind`
+//!         // implements `TryFrom<i64>`, converting the codes of variants
+//!         // without fields back.
+//!         NotFound {
+//!             description("not found")
+//!             code(404)
+//!         }
+//!
+//!         // Variants marked `retriable` make `ErrorKind::is_retriable`
+//!         // return `true`.
+//!         Timeout {
+//!             retriable
+//!             description("timed out")
+//!         }
+//!
+//!         // `public_display` declares the message returned by
+//!         // `ErrorKind::public_display`, to show to the users instead of
+//!         // the display message. It defaults to "an error occurred", and
+//!         // is forwarded by the variants of `links` and `groups`.
+//!         InvalidPassword(user: String, password: String) {
+//!             description("invalid password")
+//!             display("invalid password '{}' for {}", password, user)
+//!             public_display("invalid password for {}", user)
+//!         }
+//!
+//!         // `display_with_cause` declares the display message with a
+//!         // closure given the formatter, the fields (as a tuple if there
+//!         // are several) and the cause of the error. The cause is only
+//!         // known to the `Error`, so the `Display` of the bare `ErrorKind`
+//!         // falls back to the description.
+//!         LoadFailed(path: String) {
+//!             description("load failed")
+//!             display_with_cause(|f, path, cause| match cause {
+//!                 Some(cause) => write!(f, "failed to load {}: {}", path, cause),
+//!                 None => write!(f, "failed to load {}", path),
+//!             })
+//!         }
+//!     }
+//!
+//!     // Groups of related `ErrorKind` variants. Each group defines an enum,
+//!     // here `NetworkKind`, whose variants use the same syntax as the
+//!     // `errors` section, and an `ErrorKind::Network(NetworkKind)`
+//!     // variant forwarding its description and display to it, with
+//!     // conversions from `NetworkKind`.
+//!     //
+//!     // Optionally, some attributes can be added to a group.
+//!     //
+//!     // This section can be empty.
+//!     groups {
+//!         Network(NetworkKind) {
+//!             Timeout {
+//!                 description("timed out")
+//!             }
+//!             Refused(host: String) {
+//!                 description("connection refused")
+//!                 display("connection refused by {}", host)
+//!             }
+//!         }
+//!     }
+//!
+//!     // Settings for the generated code.
+//!     //
+//!     // This section can be empty.
+//!     options {
+//!         // Prefix the display of variants with a code with `[code] `.
+//!         display_with_code;
+//!
+//!         // Format `Error` with `Debug` as with `ChainedError::display`, so that
+//!         // `unwrap` panics and errors returned from `main` show the
+//!         // whole chain and the backtrace.
+//!         debug_as_chain;
+//!
+//!         // Capture a backtrace when creating errors even if
+//!         // `RUST_BACKTRACE` is not set, unless it is set to `0`.
+//!         always_backtrace;
+//!
+//!         // Format `Error` with `Display` as the messages of the whole chain
+//!         // joined with `: `, instead of only the message of its kind.
+//!         // `to_string` then includes the causes, which are repeated by
+//!         // `ChainedError::display` and the other chain formatters.
+//!         display_full_chain;
+//!
+//!         // Lints to allow on the generated `ErrorKind` and `Error`.
+//!         allow(clippy::large_enum_variant);
 //!     }
 //! }
 //!
 //! # fn main() {}
 //! ```
 //!
-//! Each section, `types`, `links`, `foreign_links`, and `errors` may
-//! be omitted if it is empty.
+//! Each section, `types`, `links`, `flat_links`, `foreign_links`,
+//! `transparent_foreign_links`, `errors`, `groups` and `options` may be
+//! omitted if it is empty.
+//!
+//! The sections can be preceded by a `cfg(...)` directive, which gates all
+//! the generated items with this `cfg` attribute, for error families only
+//! used with some features:
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     cfg(feature = "network")
+//!
+//!     errors { Timeout }
+//! }
+//! ```
 //!
 //! This populates the module with a number of definitions,
 //! the most important of which are the `Error` type
 //! and the `ErrorKind` type. An example of generated code can be found in the
 //! [example_generated](example_generated) module.
 //!
+//! ### Borrowing errors
+//!
+//! The `Error` and `ErrorKind` types can be declared with a lifetime, so
+//! that the fields of the `errors` variants can borrow from the input:
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! error_chain! {
+//!     types {
+//!         Error<'a>, ErrorKind<'a>, ResultExt, Result;
+//!     }
+//!
+//!     errors {
+//!         Token(t: &'a str) {
+//!             description("unexpected token")
+//!             display("unexpected token: '{}'", t)
+//!         }
+//!     }
+//! }
+//!
+//! fn expect_end<'a>(input: &'a str) -> Result<'a, ()> {
+//!     match input.split_whitespace().next() {
+//!         Some(t) => Err(ErrorKind::Token(t).into()),
+//!         None => Ok(()),
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! Both types must use the same lifetime. Such errors are not `'static`,
+//! so they can't be boxed as the cause of another error, and they don't
+//! implement `ChainedError`: the `Error` only contains its `ErrorKind`,
+//! without a cause or a backtrace. Consequently only the `types`,
+//! `derive`, `errors` and `options` sections are supported, and no
+//! `ResultExt` trait is defined. To chain a borrowing error, convert its
+//! kind into an owned error of another family first.
+//!
+//! With the `scoped` option, the `Error` also borrows its cause for the
+//! same lifetime, which suits errors living in a scope or an arena:
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! error_chain! {
+//!     types {
+//!         Error<'a>, ErrorKind<'a>, ResultExt, Result;
+//!     }
+//!
+//!     errors {
+//!         Field(name: &'a str) {
+//!             description("invalid field")
+//!             display("invalid field '{}'", name)
+//!         }
+//!     }
+//!
+//!     options {
+//!         scoped;
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let cause = ::std::fmt::Error;
+//! let err = Error::with_chain(&cause, ErrorKind::Field("name"));
+//! assert!(err.scoped_cause().is_some());
+//! # }
+//! ```
+//!
+//! The constraints are heavy: the cause must outlive the error and is
+//! only reachable through `scoped_cause` or the second field, as
+//! `Error::source` requires a `'static` cause. Nothing is boxed, so there
+//! is still no backtrace and no `chain_err`, and an error can't leave the
+//! scope of the data it borrows.
+//!
 //! ## Returning new errors
 //!
 //! Introducing new error chains, with a string message:
@@ -206,8 +389,8 @@
 //! ```
 //!
 //! Note that the return type is the typedef `Result`, which is
-//! defined by the macro as `pub type Result<T> =
-//! ::std::result::Result<T, Error>`. Note that in both cases
+//! defined by the macro as `pub type Result<T, E = Error> =
+//! ::std::result::Result<T, E>`. Note that in both cases
 //! `.into()` is called to convert a type into the `Error` type; both
 //! strings and `ErrorKind` have `From` conversions to turn them into
 //! `Error`.
@@ -222,11 +405,11 @@
 //! # fn main() {}
 //! # error_chain! { errors { FooError } }
 //! fn foo() -> Result<()> {
-//!     Ok(try!(Err(ErrorKind::FooError)))
+//!     Ok(Err(ErrorKind::FooError)?)
 //! }
 //!
 //! fn bar() -> Result<()> {
-//!     Ok(try!(Err("bogus!")))
+//!     Ok(Err("bogus!")?)
 //! }
 //! ```
 //!
@@ -329,6 +512,43 @@
 //! types of all linked error chains. Linked errors do not introduce a new
 //! cause to the error chain.
 //!
+//! The linked types may have generic arguments, as in
+//! `Other(other::Error<u32>, other::ErrorKind<u32>)`.
+//!
+//! A link to the error chain of an optional dependency can be given a
+//! `#[cfg(...)]` attribute, which gates its variant, its conversions and
+//! the match arms referring to it, so the linked types don't need to exist
+//! when the dependency is disabled:
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     links {
+//!         Optional(::optional_dependency::Error, ::optional_dependency::ErrorKind)
+//!             #[cfg(feature = "optional_dependency")];
+//!     }
+//! }
+//! ```
+//!
+//! Error chains declared in the `flat_links` block are converted into an
+//! `ErrorKind::Msg` with the display of their kind instead, so no variant
+//! nests their kind. Like `links`, and unlike `foreign_links`, they don't
+//! introduce a new cause: the converted error keeps the cause and the
+//! backtrace of the linked error. The name of the link is only used as a
+//! label.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! # mod other { error_chain! {} }
+//! error_chain! {
+//!     flat_links {
+//!         Other(other::Error, other::ErrorKind);
+//!     }
+//! }
+//! ```
+//!
 //! ## Matching errors
 //!
 //! error-chain error variants are matched with simple patterns.
@@ -405,6 +625,119 @@
 //! old error is discarded; there is no "cause" created from the
 //! original error.
 //!
+//! Foreign error types must implement `std::error::Error + Send + 'static`.
+//! This is checked by an assertion generated for each foreign link, so that
+//! a missing bound is reported as failing `assert_foreign_link_bounds`:
+//!
+//! ```compile_fail
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! use std::{error, fmt};
+//! use std::rc::Rc;
+//!
+//! // Not `Send` because of the `Rc`.
+//! #[derive(Debug)]
+//! pub struct LocalError(Rc<String>);
+//!
+//! impl fmt::Display for LocalError {
+//!     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+//!         f.write_str(&self.0)
+//!     }
+//! }
+//!
+//! impl error::Error for LocalError {}
+//!
+//! error_chain! {
+//!     foreign_links {
+//!         Local(LocalError);
+//!     }
+//! }
+//! ```
+//!
+//! A foreign link can be given a `context(...)`, in which case the `From`
+//! conversion chains an `ErrorKind::Msg` with that message on top of the
+//! foreign link variant, just as `chain_err` would. This means the kind of
+//! the converted error is `Msg`, and the foreign link variant is its cause.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     foreign_links {
+//!         Io(::std::io::Error) context("while doing IO");
+//!     }
+//! }
+//! ```
+//!
+//! Converting a foreign error captures a backtrace like any other error,
+//! which can be costly for foreign errors created in hot loops. A foreign
+//! link declared `no_backtrace` never captures one. It can't be combined
+//! with `context(...)`, since chaining captures a backtrace.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     foreign_links {
+//!         Parse(::std::num::ParseIntError) no_backtrace;
+//!     }
+//! }
+//! ```
+//!
+//! A foreign link can instead be mapped to an existing unit variant of
+//! `ErrorKind` with `as`. No variant is generated for it, and the `From`
+//! conversion creates an error of that kind, with the foreign error as its
+//! cause. The name of the link is only used as a label.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     foreign_links {
+//!         Parse(::std::num::ParseIntError) as ErrorKind::InvalidInput;
+//!     }
+//!
+//!     errors {
+//!         InvalidInput {
+//!             description("invalid input")
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Foreign errors declared in the `transparent_foreign_links` block don't
+//! get their own `ErrorKind` variant: they are converted into an
+//! `ErrorKind::Msg` with the message of the foreign error, and the foreign
+//! error is kept as the cause.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     transparent_foreign_links {
+//!         Io(::std::io::Error);
+//!     }
+//! }
+//! ```
+//!
+//! There is no catch-all conversion from any `std::error::Error`: the
+//! `Error` is itself an `std::error::Error`, so a blanket
+//! `impl<E: std::error::Error> From<E> for Error` would conflict with the
+//! `impl<T> From<T> for T` of the standard library. The `catch_all_foreign`
+//! option is rejected for this reason. Each foreign error has to be
+//! declared in `transparent_foreign_links`, or be chained with
+//! `ResultExt::chain_err` before using `?`.
+//!
+//! ```compile_fail
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {}
+//! error_chain! {
+//!     options {
+//!         catch_all_foreign;
+//!     }
+//! }
+//! ```
+//!
 //! ## Backtraces
 //!
 //! If the `RUST_BACKTRACE` environment variable is set to anything
@@ -413,27 +746,160 @@
 //! and `chain_err` invocations of compatible types. To read the
 //! backtrace just call the `backtrace()` method.
 //!
-//! Backtrace generation can be disabled by turning off the `backtrace` feature.
+//! Backtrace generation can be disabled by turning off the `backtrace` feature,
+//! or at runtime with `set_backtraces_enabled`. To find out why the errors
+//! have no backtrace, `backtrace_status` tells which of the feature, the
+//! runtime setting and `RUST_BACKTRACE` prevents it.
+//!
+//! ## Timestamps
+//!
+//! With the `timestamp` feature enabled, each error records the time at
+//! which it was created, returned by `ChainedError::created_at`, to
+//! correlate it with logs.
 //!
 //! ## Iteration
 //!
 //! The `iter` method returns an iterator over the chain of error boxes.
 //!
+//! ## Converting to `anyhow::Error`
+//!
+//! With the `anyhow` feature enabled, `ChainedError::into_anyhow` converts
+//! an error chain into an `anyhow::Error`:
+//!
+//! ```ignore
+//! fn run() -> anyhow::Result<()> {
+//!     do_something().map_err(ChainedError::into_anyhow)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! `anyhow::Error` can only wrap errors that are `Sync`, while the chain
+//! holds `Send`-only boxes, so the conversion copies the message of each
+//! error in the chain; walking `source()` on the resulting `anyhow::Error`
+//! yields the same messages as `iter`. The backtrace is not carried over.
+//! For the same reason `From<Error> for anyhow::Error` can't be
+//! implemented, as it would overlap with anyhow's own blanket impl.
+//!
+//! The `failure` feature similarly provides `ChainedError::into_failure`,
+//! converting an error chain into a `failure::Error` whose causes, walked
+//! with `iter_chain`, have the messages of the error chain.
+//!
+//! ## Deserializing kinds
+//!
+//! With the `serde` feature enabled, the `deserialize_kind` option
+//! implements `serde::Deserialize` for `ErrorKind`, to rebuild the kinds of
+//! errors received over the wire. The variants of the `errors` section must
+//! not have fields: they are deserialized from their name, and `Msg` from
+//! a map like `{"Msg": "message"}`. Other names are rejected.
+//!
+//! ```ignore
+//! error_chain! {
+//!     errors {
+//!         Timeout
+//!     }
+//!
+//!     options {
+//!         deserialize_kind;
+//!     }
+//! }
+//!
+//! let kind: ErrorKind = serde_json::from_str("\"Timeout\"")?;
+//! ```
+//!
+//! ## Aggregating errors
+//!
+//! The `multi` option adds a `Multiple(Vec<Error>)` variant to the
+//! `ErrorKind`, for operations producing many independent errors, and
+//! implements `From<Vec<Error>>` for `Error`. It displays the number of
+//! errors followed by each of them inline, or `no errors` if the vector is
+//! empty. The `ErrorKind` then contains errors, so it can only derive the
+//! traits that `Error` implements, and the option is not available to
+//! families declared with a lifetime.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {
+//! error_chain! {
+//!     options {
+//!         multi;
+//!     }
+//! }
+//!
+//! let err = Error::from(vec![Error::from("bad name"), Error::from("bad age")]);
+//! assert_eq!(err.to_string(), "2 errors: [1] bad name; [2] bad age");
+//! # }
+//! ```
+//!
+//! ## Sharing causes
+//!
+//! To deliver one error to many receivers, the `shared_cause` option
+//! implements `Clone` for `Error`. The causes can't generally be cloned,
+//! so a cause must be wrapped in a `SharedCause` to be shared by reference
+//! count between the clones. This requires the cause to be `Sync`, unlike
+//! the other causes which only need to be `Send`. The errors of the chain
+//! see through the wrapper, so the `source` of both clones is the same
+//! error. When cloning, causes of the same family are cloned in turn, and
+//! other causes are replaced by an error with the messages of their chain,
+//! losing their types. The `ErrorKind` is cloned too, so it must derive
+//! `Clone`, which rules out `foreign_links` to types which are not `Clone`.
+//!
+//! ```
+//! # #[macro_use] extern crate error_chain;
+//! # fn main() {
+//! use error_chain::SharedCause;
+//!
+//! error_chain! {
+//!     derive {
+//!         Debug, Clone;
+//!     }
+//!
+//!     options {
+//!         shared_cause;
+//!     }
+//! }
+//!
+//! let cause = std::io::Error::new(std::io::ErrorKind::NotFound, "not found");
+//! let err = Error::with_chain(SharedCause::new(cause), "lookup failed");
+//! let copy = err.clone();
+//! assert_eq!(copy.iter().nth(1).unwrap().to_string(), "not found");
+//! # }
+//! ```
+//!
 //! [error-type]: https://github.com/DanielKeep/rust-error-type
 //! [quick-error]: https://github.com/tailhook/quick-error
 
 
 #[cfg(feature = "backtrace")]
 extern crate backtrace;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde_json;
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub extern crate serde_crate as serde;
+#[cfg(feature = "anyhow")]
+#[doc(hidden)]
+pub extern crate anyhow_crate as anyhow;
+#[cfg(feature = "failure")]
+#[doc(hidden)]
+pub extern crate failure_crate as failure;
 
+use std::cell::RefCell;
 use std::error;
 use std::iter::Iterator;
-#[cfg(feature = "backtrace")]
+use std::marker::PhantomData;
 use std::sync::Arc;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::ThreadId;
+use std::time::SystemTime;
+#[cfg(feature = "timestamp")]
+use std::time;
 
 #[cfg(feature = "backtrace")]
 pub use backtrace::Backtrace;
+#[cfg(feature = "backtrace")]
+pub use backtrace::BacktraceFrame;
 #[cfg(not(feature = "backtrace"))]
 /// Dummy type used when the `backtrace` feature is disabled.
 pub type Backtrace = ();
@@ -444,17 +910,24 @@ mod quick_error;
 mod error_chain;
 #[macro_use]
 mod quick_main;
-pub use quick_main::ExitCode;
+pub use quick_main::{ExitCode, IntoExitCode};
+#[doc(hidden)]
+pub use quick_main::exit_code;
+mod render;
+pub use render::{ChainRenderer, InlineRenderer, NumberedRenderer, TreeRenderer};
 #[cfg(feature = "example_generated")]
 pub mod example_generated;
 
 /// Iterator over the error chain using the `Error::cause()` method.
-pub struct ErrorChainIter<'a>(pub Option<&'a error::Error>);
+pub struct ErrorChainIter<'a>(pub Option<&'a dyn error::Error>);
 
 impl<'a> Iterator for ErrorChainIter<'a> {
-    type Item = &'a error::Error;
+    type Item = &'a dyn error::Error;
 
-    fn next<'b>(&'b mut self) -> Option<&'a error::Error> {
+    // `cause` is still walked rather than `source`, for the errors which only
+    // implement the former.
+    #[allow(deprecated)]
+    fn next<'b>(&'b mut self) -> Option<&'a dyn error::Error> {
         match self.0.take() {
             Some(e) => {
                 self.0 = e.cause();
@@ -465,86 +938,1520 @@ impl<'a> Iterator for ErrorChainIter<'a> {
     }
 }
 
+/// A backtrace along with the thread it was captured on.
+#[cfg(feature = "backtrace")]
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct InternalBacktrace {
+    backtrace: Backtrace,
+    thread_name: Option<String>,
+    thread_id: ThreadId,
+}
+
+#[cfg(feature = "backtrace")]
+impl InternalBacktrace {
+    /// Captures a backtrace of the current call stack and the current thread.
+    fn new() -> InternalBacktrace {
+        let thread = std::thread::current();
+        let backtrace = match MAX_BACKTRACE_FRAMES.load(Ordering::Relaxed) {
+            usize::MAX => Backtrace::new(),
+            max => {
+                // Only the frames capturing the backtrace, which are skipped,
+                // and the retained frames are resolved.
+                let mut frames: Vec<BacktraceFrame> = Backtrace::new_unresolved().into();
+                let skipped = frames.iter_mut()
+                    .position(|frame| {
+                        frame.resolve();
+                        !is_capture_frame(frame)
+                    })
+                    .unwrap_or(frames.len());
+                frames.drain(..skipped);
+                frames.truncate(max);
+                let mut backtrace = Backtrace::from(frames);
+                backtrace.resolve();
+                backtrace
+            }
+        };
+        InternalBacktrace {
+            backtrace,
+            thread_name: thread.name().map(|n| n.to_owned()),
+            thread_id: thread.id(),
+        }
+    }
+
+    /// Wraps a backtrace captured elsewhere, recording the current thread.
+    fn from_backtrace(backtrace: Backtrace) -> InternalBacktrace {
+        let thread = std::thread::current();
+        InternalBacktrace {
+            backtrace,
+            thread_name: thread.name().map(|n| n.to_owned()),
+            thread_id: thread.id(),
+        }
+    }
+}
+
+/// Returns `true` if `frame` belongs to error-chain or the `backtrace`
+/// crate, capturing a backtrace rather than creating an error.
+#[cfg(feature = "backtrace")]
+fn is_capture_frame(frame: &BacktraceFrame) -> bool {
+    // The last symbol is the function of the frame, the others are inlined.
+    match frame.symbols().last().and_then(|s| s.name()) {
+        Some(name) => {
+            let name = format!("{:#}", name);
+            let name = name.trim_start_matches('<');
+            name.starts_with("error_chain::") || name.starts_with("backtrace::")
+        }
+        None => false,
+    }
+}
+
 /// Returns a backtrace of the current call stack if `RUST_BACKTRACE`
-/// is set to anything but ``0``, and `None` otherwise.  This is used
+/// is set to anything but ``0`` and the backtraces are not disabled by
+/// `set_backtraces_enabled`, and `None` otherwise.  This is used
 /// in the generated error implementations.
 #[cfg(feature = "backtrace")]
 #[doc(hidden)]
-pub fn make_backtrace() -> Option<Arc<Backtrace>> {
-    match std::env::var_os("RUST_BACKTRACE") {
-        Some(ref val) if val != "0" => Some(Arc::new(Backtrace::new())),
+pub fn make_backtrace() -> Option<Arc<InternalBacktrace>> {
+    match backtrace_status() {
+        BacktraceStatus::EnvVarOn => Some(Arc::new(InternalBacktrace::new())),
         _ => None,
     }
 }
 
-/// This trait is implemented on all the errors generated by the `error_chain`
-/// macro.
-pub trait ChainedError: error::Error + Send + 'static {
-    /// Associated kind type.
-    type ErrorKind;
-
-    /// Constructs an error from a kind, and generates a backtrace.
-    fn from_kind(kind: Self::ErrorKind) -> Self where Self: Sized;
+/// Whether the errors capture a backtrace when they are created, as
+/// returned by `backtrace_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacktraceStatus {
+    /// The `backtrace` feature of error-chain is disabled, so no backtrace
+    /// is ever captured.
+    FeatureDisabled,
+    /// The backtraces are disabled by `set_backtraces_enabled`.
+    ProgrammaticallyDisabled,
+    /// `RUST_BACKTRACE` is not set, or set to `0`.
+    EnvVarOff,
+    /// `RUST_BACKTRACE` is set to anything but `0`, so backtraces are
+    /// captured.
+    EnvVarOn,
+}
 
-    /// Constructs a chained error from another error and a kind, and generates a backtrace.
-    fn with_chain<E, K>(error: E, kind: K) -> Self
-        where Self: Sized,
-              E: ::std::error::Error + Send + 'static,
-              K: Into<Self::ErrorKind>;
+/// Returns whether the errors created now capture a backtrace, and if not,
+/// the first of the `backtrace` feature, `set_backtraces_enabled` and
+/// `RUST_BACKTRACE` preventing it. Errors of families with the
+/// `always_backtrace` option also capture one if `RUST_BACKTRACE` is unset.
+pub fn backtrace_status() -> BacktraceStatus {
+    if cfg!(not(feature = "backtrace")) {
+        return BacktraceStatus::FeatureDisabled;
+    }
+    if BACKTRACES_DISABLED.load(Ordering::Relaxed) {
+        return BacktraceStatus::ProgrammaticallyDisabled;
+    }
+    match std::env::var_os("RUST_BACKTRACE") {
+        Some(ref val) if val != "0" => BacktraceStatus::EnvVarOn,
+        _ => BacktraceStatus::EnvVarOff,
+    }
+}
 
-    /// Returns the kind of the error.
-    fn kind(&self) -> &Self::ErrorKind;
+/// The location of a frame of a backtrace, as resolved by symbolication.
+///
+/// Unlike `Backtrace`, it can be compared, which allows `assert_eq!` on
+/// backtraces in tests controlling where they are captured. The comparison is
+/// only as good as the symbolication: a field which couldn't be resolved is
+/// `None`, and two unresolved frames compare equal.
+#[cfg(feature = "backtrace")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedFrame {
+    /// The name of the function.
+    pub name: Option<String>,
+    /// The source file of the function.
+    pub filename: Option<std::path::PathBuf>,
+    /// The line in the source file.
+    pub lineno: Option<u32>,
+}
+
+/// Returns the resolved frames of `backtrace`, one for each symbol. A frame
+/// of the backtrace has several symbols when functions were inlined into it.
+#[cfg(feature = "backtrace")]
+pub fn resolved_frames(backtrace: &Backtrace) -> Vec<ResolvedFrame> {
+    backtrace.frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .map(|symbol| ResolvedFrame {
+            name: symbol.name().map(|name| name.to_string()),
+            filename: symbol.filename().map(|filename| filename.to_owned()),
+            lineno: symbol.lineno(),
+        })
+        .collect()
+}
+
+/// Encodes the instruction pointers of the frames of `backtrace`, see
+/// `ChainedError::backtrace_bytes`.
+///
+/// Each pointer is stored as the difference with the previous one, zigzag
+/// encoded so small negative differences stay small, in LEB128.
+#[cfg(feature = "backtrace")]
+fn encode_backtrace(backtrace: &Backtrace) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut previous = 0usize;
+    for frame in backtrace.frames() {
+        let ip = frame.ip() as usize;
+        let delta = ip.wrapping_sub(previous) as isize as i64;
+        let mut zigzag = ((delta << 1) ^ (delta >> 63)) as u64;
+        previous = ip;
+        loop {
+            let byte = (zigzag & 0x7f) as u8;
+            zigzag >>= 7;
+            if zigzag == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+    bytes
+}
+
+/// Resolves the frames of a backtrace encoded by
+/// `ChainedError::backtrace_bytes`, in the same way as `resolved_frames`.
+///
+/// The bytes only hold the addresses of the frames, so they can only be
+/// resolved by the process which captured them: another run of the same
+/// binary is usually loaded at another address. Invalid bytes are not an
+/// error, they yield meaningless or no frames.
+#[cfg(feature = "backtrace")]
+pub fn resolve_backtrace_bytes(bytes: &[u8]) -> Vec<ResolvedFrame> {
+    let mut frames = Vec::new();
+    let mut previous = 0usize;
+    let mut zigzag = 0u64;
+    let mut shift = 0;
+    for &byte in bytes {
+        if shift < 64 {
+            zigzag |= ((byte & 0x7f) as u64) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+        let ip = previous.wrapping_add(delta as isize as usize);
+        backtrace::resolve(ip as *mut std::os::raw::c_void, |symbol| {
+            frames.push(ResolvedFrame {
+                name: symbol.name().map(|name| name.to_string()),
+                filename: symbol.filename().map(|filename| filename.to_owned()),
+                lineno: symbol.lineno(),
+            });
+        });
+        previous = ip;
+        zigzag = 0;
+        shift = 0;
+    }
+    frames
+}
+
+/// This trait is implemented on all the errors generated by the `error_chain`
+/// macro.
+pub trait ChainedError: error::Error + Send + 'static {
+    /// Associated kind type.
+    type ErrorKind;
+
+    /// Constructs an error from a kind, and generates a backtrace.
+    fn from_kind(kind: Self::ErrorKind) -> Self where Self: Sized;
+
+    /// Constructs a chained error from another error and a kind, and generates a backtrace.
+    fn with_chain<E, K>(error: E, kind: K) -> Self
+        where Self: Sized,
+              E: ::std::error::Error + Send + 'static,
+              K: Into<Self::ErrorKind>;
+
+    /// Returns the kind of the error.
+    fn kind(&self) -> &Self::ErrorKind;
+
+    /// Returns a clone of the kind of the error, for instance when `Clone`
+    /// is in the `derive` block of the family.
+    fn kind_owned(&self) -> Self::ErrorKind
+        where Self::ErrorKind: Clone
+    {
+        self.kind().clone()
+    }
 
     /// Iterates over the error chain.
-    fn iter(&self) -> ErrorChainIter;
+    fn iter(&self) -> ErrorChainIter<'_>;
+
+    /// Returns the backtrace associated with this error.
+    fn backtrace(&self) -> Option<&Backtrace>;
+
+    /// Returns the backtrace associated with this error, followed by the
+    /// backtraces captured when chaining onto it if `keep_all_backtraces` is
+    /// enabled.
+    fn backtraces(&self) -> Vec<&Backtrace>;
+
+    /// Returns the backtrace associated with this error formatted with
+    /// `{:?}`, or `None` if there is none.
+    fn backtrace_string(&self) -> Option<String> {
+        self.backtrace().map(|backtrace| format!("{:?}", backtrace))
+    }
+
+    /// Returns the resolved frames of all the backtraces of the chain, each
+    /// with the index in the chain of the error it was captured for. As with
+    /// `resolved_frames`, a frame has one entry for each of its symbols.
+    ///
+    /// A backtrace shared by several errors of the chain appears once, with
+    /// the index of the rootmost of them. Only the backtraces of errors from
+    /// this family and its links are found.
+    #[cfg(feature = "backtrace")]
+    fn all_resolved_frames(&self) -> ::std::vec::IntoIter<(usize, ResolvedFrame)>
+        where Self: Sized
+    {
+        let mut errors: Vec<&(dyn error::Error + 'static)> = vec![self];
+        while let Some(e) = errors[errors.len() - 1].source() {
+            errors.push(e);
+        }
+
+        let mut seen: Vec<Arc<InternalBacktrace>> = Vec::new();
+        let mut frames = Vec::new();
+        for (index, e) in errors.into_iter().enumerate().rev() {
+            let backtraces = Self::extract_backtrace(e)
+                .into_iter()
+                .chain(Self::extract_later_backtraces(e));
+            for backtrace in backtraces {
+                if seen.iter().any(|b| Arc::ptr_eq(b, &backtrace)) {
+                    continue;
+                }
+                frames.extend(resolved_frames(&backtrace.backtrace).into_iter().map(|f| (index, f)));
+                seen.push(backtrace);
+            }
+        }
+        frames.sort_by_key(|&(index, _)| index);
+        frames.into_iter()
+    }
+
+    /// Returns the instruction pointers of the frames of the backtrace,
+    /// without the symbols, compactly encoded for storage. They can be
+    /// resolved later with `resolve_backtrace_bytes`.
+    #[cfg(feature = "backtrace")]
+    fn backtrace_bytes(&self) -> Option<Vec<u8>> {
+        self.backtrace().map(encode_backtrace)
+    }
+
+    /// Returns the resolved frames of the backtrace whose source file is in
+    /// `crate_prefix`, for instance `src/`, leaving out the frames of the
+    /// standard library and of the dependencies. Absolute paths are
+    /// matched relative to the current directory.
+    #[cfg(feature = "backtrace")]
+    fn backtrace_user_only(&self, crate_prefix: &str) -> Option<Vec<ResolvedFrame>> {
+        let current_dir = std::env::current_dir().ok();
+        self.backtrace().map(|backtrace| {
+            resolved_frames(backtrace)
+                .into_iter()
+                .filter(|frame| match frame.filename {
+                    Some(ref filename) => {
+                        let relative = current_dir.as_ref()
+                            .and_then(|dir| filename.strip_prefix(dir).ok())
+                            .unwrap_or(filename);
+                        relative.to_string_lossy().starts_with(crate_prefix)
+                    }
+                    None => false,
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the rootmost error of the chain if it is an `E`, and `None`
+    /// otherwise, for instance when the root is a foreign error.
+    fn downcast_root<E: ChainedError>(&self) -> Option<&E>
+        where Self: Sized
+    {
+        let mut root: &(dyn error::Error + 'static) = self;
+        while let Some(e) = root.source() {
+            root = e;
+        }
+        root.downcast_ref::<E>()
+    }
+
+    /// Returns the name and id of the thread the backtrace was captured on.
+    fn thread(&self) -> Option<(Option<String>, ThreadId)>;
+
+    /// Returns the time at which this error was created. Always `None` if
+    /// the `timestamp` feature is disabled.
+    fn created_at(&self) -> Option<SystemTime>;
+
+    /// Returns the locations of the `chain_err` and `bail_traced!` calls
+    /// which built this error, from the root to the top. Always empty if the
+    /// `context-trace` feature is disabled.
+    fn context_trace(&self) -> &[(&'static str, u32)];
+
+    /// Returns the global contexts which were pushed on the current thread
+    /// when this error was created, the outermost first. See
+    /// `push_global_context`.
+    fn global_context(&self) -> &[String];
+
+    /// Returns the data which few errors have, like the fields given to
+    /// `with_field`.
+    #[doc(hidden)]
+    fn extras(&self) -> Option<&StateExtras>;
+
+    /// Attaches a field named `key` to this error, which appears in the
+    /// `fields` object of its node in `to_json_tree`. The value is
+    /// serialized right away, or replaced by `null` if it fails to.
+    #[cfg(feature = "serde")]
+    fn with_field<V>(self, key: &'static str, value: V) -> Self
+        where Self: Sized,
+              V: serde::Serialize
+    {
+        let (kind, mut state) = self.into_parts();
+        let value = serde_json::to_value(value).unwrap_or(serde_json::Value::Null);
+        state.extras_mut().fields.insert(key.to_owned(), value);
+        Self::new(kind, state)
+    }
+
+    /// Returns `true` if the rootmost error of the chain is a foreign error.
+    ///
+    /// Only errors of this same family are recognized: an error from another
+    /// `error_chain!` family used as a cause counts as foreign, and the walk
+    /// stops at the first foreign error found (it does not look through its
+    /// `cause()`). Errors created from a `foreign_links` type count as
+    /// foreign.
+    fn has_foreign_root(&self) -> bool;
+
+    /// Returns the names of the nested kind variants, joined by `.`, as in
+    /// `Utils.BadStuff`. Each `links` variant is followed into the kind of
+    /// the linked error; foreign links end the path.
+    fn kind_path(&self) -> String;
+
+    /// Returns the name of the variant of the kind of this error.
+    fn kind_name(&self) -> &'static str;
+
+    /// Boxes the error into a `BoxedChainError`, which can hold errors of
+    /// any family.
+    fn into_boxed_chain(self) -> BoxedChainError
+        where Self: Sized
+    {
+        BoxedChainError(Box::new(self))
+    }
+
+    /// Returns the number of errors in the chain, including this one.
+    fn chain_len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Counts the causes of this error, excluding itself, which are errors
+    /// of the same family and the ones which are not.
+    fn cause_stats(&self) -> CauseStats
+        where Self: Sized
+    {
+        let mut stats = CauseStats::default();
+        let mut cause = error::Error::source(self);
+        while let Some(e) = cause {
+            if e.is::<Self>() {
+                stats.family += 1;
+            } else {
+                stats.foreign += 1;
+            }
+            cause = e.source();
+        }
+        stats
+    }
+
+    /// Returns an iterator over the error chain, pairing each error with
+    /// its own backtrace. Only the errors of the same family have one, the
+    /// other errors are paired with `None`.
+    fn iter_with_backtrace(&self) -> BacktraceIter<'_, Self>
+        where Self: Sized
+    {
+        BacktraceIter {
+            next: Some(self),
+            family: PhantomData,
+        }
+    }
+
+    /// Builds an equivalent error of the `E` family, whose chain has the
+    /// messages of the chain of this error as `Msg`s. The kinds and the
+    /// backtrace are lost, and the new errors capture their own backtrace.
+    fn transcribe<E>(&self) -> E
+        where E: ChainedError,
+              E::ErrorKind: From<String>
+    {
+        let mut messages: Vec<String> = self.iter().map(|e| e.to_string()).collect();
+        let root = messages.pop().unwrap_or_default();
+        messages.into_iter()
+            .rev()
+            .fold(E::from_kind(root.into()), |cause, message| E::with_chain(cause, message))
+    }
+
+    /// Returns a snapshot of the chain of this error, with one boxed error
+    /// per error of the chain which displays the same message.
+    ///
+    /// The errors of the chain can't be cloned, so the snapshot only keeps
+    /// their messages, and the boxed errors have no cause of their own.
+    fn clone_boxed_chain(&self) -> Vec<Box<dyn error::Error + Send>> {
+        self.iter()
+            .map(|e| {
+                let boxed: Box<dyn error::Error + Send + Sync> = e.to_string().into();
+                boxed as Box<dyn error::Error + Send>
+            })
+            .collect()
+    }
+
+    /// Returns an iterator over the causes of this error, excluding itself,
+    /// which are not errors of the same family.
+    fn foreign_causes(&self) -> ForeignCauses<'_, Self>
+        where Self: Sized
+    {
+        ForeignCauses {
+            next: error::Error::source(self),
+            family: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the kinds of this error and `other` are the same
+    /// variant, regardless of their fields and of the causes of the errors.
+    fn same_kind_as(&self, other: &Self) -> bool
+        where Self: Sized
+    {
+        ::std::mem::discriminant(self.kind()) == ::std::mem::discriminant(other.kind())
+    }
+
+    /// Returns the error at `depth` in the chain, where `0` is this error
+    /// and each following depth is the cause of the previous one, or `None`
+    /// if the chain is shorter than that.
+    fn error_at(&self, depth: usize) -> Option<&dyn error::Error> {
+        self.iter().nth(depth)
+    }
+
+    /// Calls `visitor` with the depth and each error of the chain, from this
+    /// error, at depth `0`, to the rootmost cause.
+    fn walk<V>(&self, mut visitor: V)
+        where V: FnMut(usize, &dyn error::Error)
+    {
+        for (depth, e) in self.iter().enumerate() {
+            visitor(depth, e);
+        }
+    }
+
+    /// Returns the description of the deepest error of the chain which has a
+    /// meaningful one, on the basis that the rootmost errors are the most
+    /// specific. Empty descriptions are skipped, as well as the placeholder
+    /// returned by errors which don't implement `description`.
+    #[allow(deprecated)]
+    fn best_description(&self) -> &str {
+        const PLACEHOLDER: &str = "description() is deprecated; use Display";
+        self.iter()
+            .map(|e| e.description())
+            .filter(|d| !d.is_empty() && *d != PLACEHOLDER)
+            .last()
+            .unwrap_or_else(|| self.description())
+    }
+
+    /// Returns a one-line summary of the error: its message followed by the
+    /// number of causes, as in `boom (+2 causes)`.
+    fn summarize(&self) -> String {
+        match self.chain_len() - 1 {
+            0 => self.to_string(),
+            1 => format!("{} (+1 cause)", self),
+            n => format!("{} (+{} causes)", self, n),
+        }
+    }
+
+    /// Returns an object which implements `Display` for printing the full
+    /// context of this error.
+    ///
+    /// The full cause chain and backtrace, if present, will be printed.
+    fn display<'a>(&'a self) -> Display<'a, Self> {
+        Display(self)
+    }
+
+    /// Like `display`, but a `Context: ` line with the global contexts of the
+    /// error is printed after the causes, and a `thread: ` line with the name
+    /// of the thread the backtrace was captured on before the backtrace.
+    fn display_detailed<'a>(&'a self) -> DetailedDisplay<'a, Self> {
+        DetailedDisplay(self)
+    }
+
+    /// Like `display`, but the backtrace is replaced by a fixed
+    /// `<backtrace omitted>` line, so the output is deterministic. Useful for
+    /// snapshot testing.
+    fn display_chain_stable<'a>(&'a self) -> StableDisplay<'a, Self> {
+        StableDisplay(self)
+    }
+
+    /// Like `display`, but each cause is indented by two more spaces than
+    /// the error it caused and prefixed with `caused by:`, so that the chain
+    /// reads as a tree. The backtrace is indented like the deepest cause.
+    fn display_chain_indented<'a>(&'a self) -> IndentedDisplay<'a, Self> {
+        IndentedDisplay(self)
+    }
+
+    /// Like `display`, but the error is printed in red, the causes are
+    /// dimmed and the backtrace is printed in gray, with ANSI escape codes.
+    #[cfg(feature = "color")]
+    fn display_chain_colored<'a>(&'a self) -> ColoredDisplay<'a, Self> {
+        ColoredDisplay(self, true)
+    }
+
+    /// Like `display_chain_colored`, but the colors are only used if
+    /// `is_tty` is `true`, so that the output written to files and logs
+    /// doesn't contain escape codes. Detecting whether the output is a
+    /// terminal is left to the caller.
+    #[cfg(feature = "color")]
+    fn display_chain_colored_if_tty<'a>(&'a self, is_tty: bool) -> ColoredDisplay<'a, Self> {
+        ColoredDisplay(self, is_tty)
+    }
+
+    /// Like `display`, but the top line is only prefixed with `Error: ` if
+    /// `prefix` is `true`, for logs which already prefix their lines with a
+    /// level.
+    fn display_chain_with_prefix<'a>(&'a self, prefix: bool) -> PrefixDisplay<'a, Self> {
+        PrefixDisplay(self, prefix)
+    }
+
+    /// Like `display`, but the backtrace is never printed.
+    fn display_chain_no_backtrace<'a>(&'a self) -> NoBacktraceDisplay<'a, Self> {
+        NoBacktraceDisplay(self)
+    }
+
+    /// Returns the `std::error::Report` of this error, showing the causes
+    /// on separate lines and the backtrace provided through the standard
+    /// library, if any.
+    ///
+    /// `std::error::Report` is unstable, so this requires a nightly
+    /// compiler and building with `RUSTFLAGS="--cfg error_chain_nightly"`.
+    #[cfg(error_chain_nightly)]
+    fn to_report(&self) -> error::Report<&Self>
+        where Self: Sized
+    {
+        error::Report::new(self).pretty(true).show_backtrace(true)
+    }
+
+    /// Returns a report of everything known about this error, for crash
+    /// handlers: the message of this error, the numbered causes, the thread
+    /// the backtrace was captured on and the backtraces, each in a section
+    /// with a `== Name ==` header.
+    fn format_report(&self) -> String {
+        use std::fmt::Write;
+
+        let mut report = String::new();
+        let _ = writeln!(report, "== Summary ==\n{}\n", self);
+        let _ = writeln!(report, "== Causes ==");
+        let mut causes = self.iter().skip(1).peekable();
+        if causes.peek().is_none() {
+            let _ = writeln!(report, "none");
+        }
+        for (i, e) in causes.enumerate() {
+            let _ = writeln!(report, "{}. {}", i + 1, e);
+        }
+        let _ = writeln!(report, "\n== Thread ==");
+        match self.thread() {
+            Some((name, id)) => {
+                let name = name.as_ref().map_or("<unnamed>", |n| &n[..]);
+                let _ = writeln!(report, "{} ({:?})", name, id);
+            }
+            None => {
+                let _ = writeln!(report, "unknown");
+            }
+        }
+        let _ = writeln!(report, "\n== Backtrace ==");
+        let backtraces = self.backtraces();
+        if backtraces.is_empty() {
+            let _ = writeln!(report, "not captured");
+        }
+        for (i, backtrace) in backtraces.iter().enumerate() {
+            if backtraces.len() > 1 {
+                let _ = writeln!(report, "Backtrace {}:", i + 1);
+            }
+            let _ = writeln!(report, "{:?}", backtrace);
+        }
+        report
+    }
+
+    /// Renders the error chain with `renderer`. See `ChainRenderer`.
+    fn render_with<R: ChainRenderer>(&self, renderer: &R) -> String
+        where Self: Sized
+    {
+        renderer.render(self)
+    }
+
+    /// Returns an object which implements `Display` for printing the
+    /// messages of the error chain on a single line, separated by `: `.
+    fn display_chain_inline<'a>(&'a self) -> InlineDisplay<'a, Self> {
+        InlineDisplay(self)
+    }
+
+    /// Renders the error chain as nested JSON objects of the form
+    /// `{ "message": ..., "cause": ... }`. The rootmost error has a `null`
+    /// cause. The errors of this family with fields attached by
+    /// `with_field` also have a `fields` object.
+    #[cfg(feature = "serde")]
+    fn to_json_tree(&self) -> serde_json::Value
+        where Self: Sized
+    {
+        let mut levels = Vec::new();
+        let mut next: Option<&(dyn error::Error + 'static)> = Some(self);
+        while let Some(e) = next {
+            let fields = e.downcast_ref::<Self>()
+                .and_then(|e| e.extras())
+                .map(|extras| extras.fields.clone())
+                .filter(|fields| !fields.is_empty());
+            levels.push((e.to_string(), fields));
+            next = e.source();
+        }
+        levels.into_iter().rev().fold(serde_json::Value::Null, |cause, (message, fields)| {
+            let mut node = serde_json::Map::new();
+            node.insert("message".to_owned(), serde_json::Value::String(message));
+            if let Some(fields) = fields {
+                node.insert("fields".to_owned(), serde_json::Value::Object(fields));
+            }
+            node.insert("cause".to_owned(), cause);
+            serde_json::Value::Object(node)
+        })
+    }
+
+    /// Converts the error chain into an `anyhow::Error`, keeping the message
+    /// of every error in the chain.
+    #[cfg(feature = "anyhow")]
+    fn into_anyhow(self) -> anyhow::Error
+        where Self: Sized
+    {
+        let mut messages: Vec<String> = self.iter().map(|e| e.to_string()).collect();
+        let root = anyhow::Error::msg(messages.pop().expect("error chain is never empty"));
+        messages.into_iter().rev().fold(root, |cause, message| cause.context(message))
+    }
+
+    /// Converts the error chain into a `failure::Error`, keeping the message
+    /// of every error in the chain.
+    #[cfg(feature = "failure")]
+    fn into_failure(self) -> failure::Error
+        where Self: Sized
+    {
+        let mut messages: Vec<String> = self.iter().map(|e| e.to_string()).collect();
+        let root = failure::err_msg(messages.pop().expect("error chain is never empty"));
+        messages.into_iter().rev().fold(root, |cause, message| cause.context(message).into())
+    }
+
+    /// Extends the error chain with a new entry.
+    fn chain_err<F, EK>(self, error: F) -> Self
+        where F: FnOnce() -> EK,
+              EK: Into<Self::ErrorKind>;
+
+    /// Creates an error from its parts.
+    #[doc(hidden)]
+    fn new(kind: Self::ErrorKind, state: State) -> Self where Self: Sized;
+
+    /// Splits the error into its parts.
+    #[doc(hidden)]
+    fn into_parts(self) -> (Self::ErrorKind, State) where Self: Sized;
+
+    /// Returns the first known backtrace, either from its State or from one
+    /// of the errors from `foreign_links`.
+    #[cfg(feature = "backtrace")]
+    #[doc(hidden)]
+    fn extract_backtrace(e: &(dyn error::Error + 'static)) -> Option<Arc<InternalBacktrace>>
+        where Self: Sized;
+
+    /// Returns the backtraces captured after the first one, see
+    /// `keep_all_backtraces`.
+    #[cfg(feature = "backtrace")]
+    #[doc(hidden)]
+    fn extract_later_backtraces(e: &(dyn error::Error + 'static))
+        -> Vec<Arc<InternalBacktrace>>
+        where Self: Sized;
+
+    /// Returns the context trace of `e` if it is one of the errors from this
+    /// chain or its links.
+    #[cfg(feature = "context-trace")]
+    #[doc(hidden)]
+    fn extract_context_trace(e: &(dyn error::Error + 'static)) -> Vec<(&'static str, u32)>
+        where Self: Sized;
+
+    /// Records a location in the context trace.
+    #[doc(hidden)]
+    fn push_context(&mut self, file: &'static str, line: u32);
+}
+
+/// Records a location in the context trace of `e`. Used by `bail_traced!`.
+#[cfg(feature = "context-trace")]
+#[doc(hidden)]
+pub fn with_context<E: ChainedError>(mut e: E, file: &'static str, line: u32) -> E {
+    e.push_context(file, line);
+    e
+}
+
+/// Records a location in the context trace of `e`. Used by `bail_traced!`.
+#[cfg(not(feature = "context-trace"))]
+#[doc(hidden)]
+pub fn with_context<E: ChainedError>(e: E, _file: &'static str, _line: u32) -> E {
+    e
+}
+
+/// Settings from the `options` block of `error_chain!`, implemented on the
+/// generated `ErrorKind`.
+#[doc(hidden)]
+pub trait ErrorChainOptions {
+    /// Prefix the display of kinds declaring a `code` with `[code] `.
+    const DISPLAY_WITH_CODE: bool = false;
+
+    /// Format the `Error` with `Debug` as with `ChainedError::display`.
+    const DEBUG_AS_CHAIN: bool = false;
+
+    /// Format the `Error` with `Display` followed by the messages of its
+    /// causes.
+    const DISPLAY_FULL_CHAIN: bool = false;
+
+    /// Capture a backtrace for the errors of the family even if
+    /// `RUST_BACKTRACE` is not set.
+    const ALWAYS_BACKTRACE: bool = false;
+
+    /// Builds the kind of the errors converted from a `String`, like those
+    /// created by `bail!` with a formatted string, `Msg` unless
+    /// `bail_kind(...)` is set.
+    fn bail_kind(message: String) -> Self
+        where Self: Sized + From<String>
+    {
+        message.into()
+    }
+}
+
+/// A `Result`, used by `ResultExt::chain_nested` to name the types of the
+/// inner `Result`.
+#[doc(hidden)]
+pub trait NestedResult {
+    /// The type of the value.
+    type Ok;
+    /// The type of the error.
+    type Err;
+
+    /// Returns the `Result`.
+    fn into_result(self) -> Result<Self::Ok, Self::Err>;
+}
+
+impl<T, E> NestedResult for Result<T, E> {
+    type Ok = T;
+    type Err = E;
+
+    fn into_result(self) -> Result<T, E> {
+        self
+    }
+}
+
+/// A struct which formats an error for output.
+#[derive(Debug)]
+pub struct Display<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for Display<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_chain(self.0, fmt)?;
+        fmt_backtrace(self.0, fmt)
+    }
+}
+
+/// A struct which formats an error for output like `Display`, with the
+/// global contexts and the thread the backtrace was captured on.
+#[derive(Debug)]
+pub struct DetailedDisplay<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for DetailedDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_chain(self.0, fmt)?;
+        if !self.0.global_context().is_empty() {
+            writeln!(fmt, "Context: {}", self.0.global_context().join(" > "))?;
+        }
+        if self.0.backtrace().is_some() {
+            if let Some((name, _)) = self.0.thread() {
+                writeln!(fmt, "thread: {}", name.as_ref().map_or("<unnamed>", |n| &n[..]))?;
+            }
+        }
+        fmt_backtrace(self.0, fmt)
+    }
+}
+
+/// Writes the backtraces of `e`, if it has a backtrace.
+fn fmt_backtrace<T: ChainedError>(e: &T, fmt: &mut fmt::Formatter) -> fmt::Result {
+    if let Some(backtrace) = e.backtrace() {
+        let backtraces = e.backtraces();
+        if backtraces.len() > 1 {
+            for (i, backtrace) in backtraces.iter().enumerate() {
+                writeln!(fmt, "Backtrace {}:", i + 1)?;
+                writeln!(fmt, "{:?}", backtrace)?;
+            }
+        } else {
+            writeln!(fmt, "{:?}", backtrace)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A struct which formats an error for output like `Display`, with the
+/// `Error: ` prefix of the top line only if it is enabled.
+#[derive(Debug)]
+pub struct PrefixDisplay<'a, T: 'a + ?Sized>(&'a T, bool);
+
+impl<'a, T> fmt::Display for PrefixDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_chain_prefixed(self.0, fmt, self.1)?;
+        fmt_backtrace(self.0, fmt)
+    }
+}
+
+/// A struct which formats an error for output like `Display`, with ANSI
+/// colors if they are enabled.
+#[cfg(feature = "color")]
+#[derive(Debug)]
+pub struct ColoredDisplay<'a, T: 'a + ?Sized>(&'a T, bool);
+
+#[cfg(feature = "color")]
+impl<'a, T> fmt::Display for ColoredDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (red, dim, gray, reset) = if self.1 {
+            ("\x1b[31m", "\x1b[2m", "\x1b[90m", "\x1b[0m")
+        } else {
+            ("", "", "", "")
+        };
+
+        writeln!(fmt, "{}Error: {}{}", red, self.0, reset)?;
+
+        for e in self.0.iter().skip(1) {
+            writeln!(fmt, "{}Caused by: {}{}", dim, e, reset)?;
+        }
+
+        if self.0.backtrace().is_some() {
+            fmt.write_str(gray)?;
+            fmt_backtrace(self.0, fmt)?;
+            fmt.write_str(reset)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A struct which formats an error for output, without the backtrace.
+#[derive(Debug)]
+pub struct StableDisplay<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for StableDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_chain(self.0, fmt)?;
+
+        if self.0.backtrace().is_some() {
+            writeln!(fmt, "<backtrace omitted>")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A struct which formats an error chain for output, never printing the
+/// backtrace.
+#[derive(Debug)]
+pub struct NoBacktraceDisplay<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for NoBacktraceDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_chain(self.0, fmt)
+    }
+}
+
+/// The numbers of causes of an error, see `ChainedError::cause_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CauseStats {
+    /// The number of causes of the same family as the error.
+    pub family: usize,
+    /// The number of causes of other types.
+    pub foreign: usize,
+}
+
+/// Iterator over an error chain pairing each error with its backtrace, see
+/// `ChainedError::iter_with_backtrace`.
+pub struct BacktraceIter<'a, T> {
+    next: Option<&'a (dyn error::Error + 'static)>,
+    family: PhantomData<T>,
+}
+
+impl<'a, T: ChainedError> Iterator for BacktraceIter<'a, T> {
+    type Item = (&'a (dyn error::Error + 'static), Option<&'a Backtrace>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.take().map(|e| {
+            self.next = e.source();
+            (e, e.downcast_ref::<T>().and_then(|e| e.backtrace()))
+        })
+    }
+}
+
+/// Iterator over the causes of an error which are not of its family, see
+/// `ChainedError::foreign_causes`.
+pub struct ForeignCauses<'a, T> {
+    next: Option<&'a (dyn error::Error + 'static)>,
+    family: PhantomData<T>,
+}
+
+impl<'a, T: error::Error + 'static> Iterator for ForeignCauses<'a, T> {
+    type Item = &'a (dyn error::Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(e) = self.next.take() {
+            self.next = e.source();
+            if !e.is::<T>() {
+                return Some(e);
+            }
+        }
+        None
+    }
+}
+
+/// A struct which formats the messages of an error chain on a single line.
+#[derive(Debug)]
+pub struct InlineDisplay<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for InlineDisplay<'a, T>
+    where T: error::Error
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in ErrorChainIter(Some(self.0)).enumerate() {
+            if i > 0 {
+                fmt.write_str(": ")?;
+            }
+            write!(fmt, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats the messages of the chain of any error on a single line, as
+/// `ChainedError::display_chain_inline` does. Used by `ResultExt`.
+#[doc(hidden)]
+pub fn display_inline<E: error::Error>(e: &E) -> InlineDisplay<'_, E> {
+    InlineDisplay(e)
+}
+
+/// Calls the closure given to `display_with_cause(...)` with the fields of
+/// the variant, as a tuple if there are several, and the cause. Used by
+/// `quick_error`, as going through this signature lets the types of the
+/// parameters of the closure be inferred.
+#[doc(hidden)]
+pub fn display_with_cause<F, T>(display: F,
+                                fmt: &mut fmt::Formatter,
+                                fields: T,
+                                cause: Option<&dyn error::Error>)
+                                -> fmt::Result
+    where F: FnOnce(&mut fmt::Formatter, T, Option<&dyn error::Error>) -> fmt::Result
+{
+    display(fmt, fields, cause)
+}
+
+/// Formats the errors of the `Multiple` kind generated by the `multi` option
+/// as `3 errors: [1] a; [2] b; [3] c`, or `no errors` if there are none.
+#[doc(hidden)]
+pub fn display_multiple<E: fmt::Display>(errors: &[E]) -> String {
+    if errors.is_empty() {
+        return "no errors".to_owned();
+    }
+    let mut s = if errors.len() == 1 {
+        "1 error:".to_owned()
+    } else {
+        format!("{} errors:", errors.len())
+    };
+    for (i, e) in errors.iter().enumerate() {
+        if i > 0 {
+            s.push(';');
+        }
+        s.push_str(&format!(" [{}] {}", i + 1, e));
+    }
+    s
+}
+
+/// Panics with `msg` followed by the chain of `e`, formatted as by
+/// `ChainedError::display` if it is a `CE`. Used by `ResultExt::expect_chain`.
+#[doc(hidden)]
+#[track_caller]
+pub fn expect_chain_failed<CE: ChainedError>(msg: &str, e: Box<dyn error::Error + Send>) -> ! {
+    match e.downcast::<CE>() {
+        Ok(e) => panic!("{}: {}", msg, e.display()),
+        Err(e) => {
+            let mut chain = format!("Error: {}\n", e);
+            for cause in ErrorChainIter(Some(&*e)).skip(1) {
+                chain.push_str(&format!("Caused by: {}\n", cause));
+            }
+            panic!("{}: {}", msg, chain)
+        }
+    }
+}
+
+/// A struct which formats an error chain for output, indenting each cause
+/// by two more spaces than the error it caused.
+#[derive(Debug)]
+pub struct IndentedDisplay<'a, T: 'a + ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for IndentedDisplay<'a, T>
+    where T: ChainedError
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "Error: {}", self.0)?;
+
+        let mut indent = String::new();
+        for e in self.0.iter().skip(1) {
+            indent.push_str("  ");
+            writeln!(fmt, "{}caused by: {}", indent, e)?;
+        }
+
+        if let Some(backtrace) = self.0.backtrace() {
+            let backtraces = self.0.backtraces();
+            if backtraces.len() > 1 {
+                for (i, backtrace) in backtraces.iter().enumerate() {
+                    writeln!(fmt, "{}Backtrace {}:", indent, i + 1)?;
+                    fmt_indented(fmt, &indent, backtrace)?;
+                }
+            } else {
+                fmt_indented(fmt, &indent, backtrace)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the debug output of `backtrace`, with `indent` before each line.
+fn fmt_indented(fmt: &mut fmt::Formatter, indent: &str, backtrace: &Backtrace) -> fmt::Result {
+    for line in format!("{:?}", backtrace).lines() {
+        writeln!(fmt, "{}{}", indent, line)?;
+    }
+    Ok(())
+}
+
+/// Writes the messages of the error chain, one per line.
+fn fmt_chain<T: ChainedError>(e: &T, fmt: &mut fmt::Formatter) -> fmt::Result {
+    fmt_chain_prefixed(e, fmt, true)
+}
+
+/// Like `fmt_chain`, printing the `Error: ` prefix only if `prefix` is set.
+fn fmt_chain_prefixed<T: ChainedError>(e: &T, fmt: &mut fmt::Formatter, prefix: bool)
+    -> fmt::Result
+{
+    if prefix {
+        writeln!(fmt, "Error: {}", e)?;
+    } else {
+        writeln!(fmt, "{}", e)?;
+    }
+
+    for e in e.iter().skip(1) {
+        writeln!(fmt, "Caused by: {}", e)?;
+    }
+
+    Ok(())
+}
+
+/// Aggregates several errors into a single error chain.
+///
+/// The first pushed error is the outermost one, and each error is the cause
+/// of the rootmost error of the chain pushed before it, so every error keeps
+/// its own causes. The causes which are not errors of the family can't be
+/// given a cause, so if the chain of a pushed error ends with such errors,
+/// they are replaced by an error of the family with their messages joined by
+/// `": "`, whose cause is the next pushed error. The resulting chain is
+/// limited by `set_max_chain_depth`, as if it was built by `chain_err`.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # error_chain! {}
+/// # fn main() {
+/// use error_chain::ErrorAccumulator;
+///
+/// let mut errors = ErrorAccumulator::new();
+/// for i in 0..3 {
+///     errors.push(Error::from(format!("error {}", i)));
+/// }
+/// assert_eq!(errors.into_error().unwrap().iter().count(), 3);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ErrorAccumulator<E> {
+    errors: Vec<E>,
+}
+
+impl<E: ChainedError> ErrorAccumulator<E> {
+    /// Creates an empty accumulator.
+    pub fn new() -> ErrorAccumulator<E> {
+        ErrorAccumulator { errors: Vec::new() }
+    }
+
+    /// Adds an error, which will be the cause of the previously pushed one.
+    pub fn push(&mut self, e: E) {
+        self.errors.push(e);
+    }
+
+    /// Returns `true` if no error was pushed.
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Folds all the pushed errors into one chain, or returns `None` if no
+    /// error was pushed.
+    pub fn into_error(mut self) -> Option<E>
+        where E::ErrorKind: From<String>
+    {
+        let mut error = self.errors.pop()?;
+        while let Some(e) = self.errors.pop() {
+            error = attach_root(e, error);
+        }
+        let (kind, mut state) = error.into_parts();
+        state.next_error = state.next_error.and_then(limit_chain_depth);
+        Some(E::new(kind, state))
+    }
+}
+
+/// Makes `root` the cause of the rootmost error of the chain of `e`, see
+/// `ErrorAccumulator`.
+fn attach_root<E>(e: E, root: E) -> E
+    where E: ChainedError,
+          E::ErrorKind: From<String>
+{
+    let (kind, mut state) = e.into_parts();
+    let next: E = match state.next_error.take() {
+        None => root,
+        Some(next) => match next.downcast::<E>() {
+            Ok(next) => attach_root(*next, root),
+            Err(foreign) => {
+                let messages: Vec<String> =
+                    ErrorChainIter(Some(&*foreign)).map(|e| e.to_string()).collect();
+                let (collapsed, mut collapsed_state) =
+                    E::from_kind(messages.join(": ").into()).into_parts();
+                collapsed_state.next_error = Some(Box::new(root));
+                E::new(collapsed, collapsed_state)
+            }
+        },
+    };
+    state.next_error = Some(Box::new(next));
+    E::new(kind, state)
+}
+
+impl<E: ChainedError> Default for ErrorAccumulator<E> {
+    fn default() -> ErrorAccumulator<E> {
+        ErrorAccumulator::new()
+    }
+}
+
+impl<E: ChainedError> Extend<E> for ErrorAccumulator<E> {
+    fn extend<I: IntoIterator<Item = E>>(&mut self, iter: I) {
+        self.errors.extend(iter);
+    }
+}
+
+/// The methods of `ChainedError` usable on a trait object, implemented for
+/// all the `ChainedError`s. Used by `BoxedChainError` and `ChainRenderer`.
+pub trait AnyChained: error::Error + Send + 'static {
+    /// Returns `ChainedError::kind_name`.
+    fn any_kind_name(&self) -> &'static str;
+
+    /// Returns `ChainedError::iter`.
+    fn any_iter(&self) -> ErrorChainIter<'_>;
+
+    /// Writes `ChainedError::display`.
+    fn any_fmt_chain(&self, fmt: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl<T: ChainedError> AnyChained for T {
+    fn any_kind_name(&self) -> &'static str {
+        self.kind_name()
+    }
+
+    fn any_iter(&self) -> ErrorChainIter<'_> {
+        self.iter()
+    }
+
+    fn any_fmt_chain(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.display(), fmt)
+    }
+}
+
+/// An error of any family, see `ChainedError::into_boxed_chain`.
+///
+/// This allows storing errors of several families together while still
+/// being able to print their chains.
+#[derive(Debug)]
+pub struct BoxedChainError(Box<dyn AnyChained>);
+
+impl BoxedChainError {
+    /// Returns the name of the variant of the kind of the error.
+    pub fn kind_name(&self) -> &'static str {
+        self.0.any_kind_name()
+    }
+
+    /// Iterates over the error chain.
+    pub fn iter(&self) -> ErrorChainIter<'_> {
+        self.0.any_iter()
+    }
+
+    /// Renders the error chain with `renderer`. See `ChainRenderer`.
+    pub fn render_with<R: ChainRenderer>(&self, renderer: &R) -> String {
+        renderer.render(&*self.0)
+    }
+
+    /// Returns an object which implements `Display` for printing the full
+    /// context of this error, as `ChainedError::display` does.
+    pub fn display_chain(&self) -> BoxedDisplay<'_> {
+        BoxedDisplay(self)
+    }
+}
+
+impl fmt::Display for BoxedChainError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, fmt)
+    }
+}
+
+impl error::Error for BoxedChainError {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.0.source()
+    }
+}
 
-    /// Returns the backtrace associated with this error.
-    fn backtrace(&self) -> Option<&Backtrace>;
+/// A struct which formats the chain of a `BoxedChainError` for output.
+#[derive(Debug)]
+pub struct BoxedDisplay<'a>(&'a BoxedChainError);
 
-    /// Returns an object which implements `Display` for printing the full
-    /// context of this error.
-    ///
-    /// The full cause chain and backtrace, if present, will be printed.
-    fn display<'a>(&'a self) -> Display<'a, Self> {
-        Display(self)
+impl<'a> fmt::Display for BoxedDisplay<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        (self.0).0.any_fmt_chain(fmt)
     }
+}
 
-    /// Extends the error chain with a new entry.
-    fn chain_err<F, EK>(self, error: F) -> Self
-        where F: FnOnce() -> EK,
-              EK: Into<Self::ErrorKind>;
+/// The parts of an error, see the `from_parts` and `into_parts` methods of
+/// the generated `Error`.
+#[derive(Debug)]
+pub struct ErrorParts<K> {
+    /// The kind of the error.
+    pub kind: K,
+    /// The next error in the chain.
+    pub cause: Option<Box<dyn error::Error + Send>>,
+    /// The backtrace of the error. Always `None` if the `backtrace` feature
+    /// is disabled.
+    pub backtrace: Option<Backtrace>,
+}
 
-    /// Creates an error from its parts.
-    #[doc(hidden)]
-    fn new(kind: Self::ErrorKind, state: State) -> Self where Self: Sized;
+/// A plain report of an error chain, decoupled from the error types.
+///
+/// See the `into_report` method of the generated `ResultExt` trait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    /// The message of the error.
+    pub summary: String,
+    /// The messages of the causes of the error, from the top to the root.
+    pub causes: Vec<String>,
+    /// The formatted backtrace, if any.
+    pub backtrace: Option<String>,
+}
 
-    /// Returns the first known backtrace, either from its State or from one
-    /// of the errors from `foreign_links`.
-    #[cfg(feature = "backtrace")]
+impl ErrorReport {
+    /// Builds the report of `e`, looking for a backtrace in the errors of
+    /// the `CE` family.
     #[doc(hidden)]
-    fn extract_backtrace(e: &(error::Error + Send + 'static)) -> Option<Arc<Backtrace>>
-        where Self: Sized;
+    pub fn new<CE: ChainedError>(e: &(dyn error::Error + Send + 'static)) -> ErrorReport {
+        #[cfg(feature = "backtrace")]
+        let backtrace = CE::extract_backtrace(e).map(|b| format!("{:?}", b.backtrace));
+        #[cfg(not(feature = "backtrace"))]
+        let backtrace = None;
+        ErrorReport {
+            summary: e.to_string(),
+            causes: ErrorChainIter(Some(e)).skip(1).map(|c| c.to_string()).collect(),
+            backtrace,
+        }
+    }
 }
 
-/// A struct which formats an error for output.
+/// Maximum length of the error chains, `usize::MAX` if unlimited.
+static MAX_CHAIN_DEPTH: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the maximum length of the error chains built by `chain_err` and
+/// `with_chain`, or removes the limit with `None`. There is no limit by
+/// default.
+///
+/// When chaining onto an error would make the chain longer than the limit,
+/// all the errors of the existing chain are collapsed into a single cause
+/// whose message is their messages joined by `": "`, so the new chain is two
+/// errors long. With a limit of one, the existing chain is dropped instead.
+/// The backtrace of the existing chain is kept in both cases.
+pub fn set_max_chain_depth(depth: Option<usize>) {
+    MAX_CHAIN_DEPTH.store(depth.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Maximum number of frames of the captured backtraces, `usize::MAX` if
+/// unlimited.
+static MAX_BACKTRACE_FRAMES: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the maximum number of frames kept when capturing a backtrace, or
+/// removes the limit with `None`. There is no limit by default.
+///
+/// The frames of error-chain capturing the backtrace are skipped, then only
+/// the frames closest to the capture are kept, and only they are resolved,
+/// which bounds both the memory used by the backtraces and the cost of
+/// their symbolication. Whether a backtrace is captured at all is
+/// still decided by `RUST_BACKTRACE`.
+pub fn set_max_backtrace_frames(frames: Option<usize>) {
+    MAX_BACKTRACE_FRAMES.store(frames.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Whether `set_backtraces_enabled` disabled the backtraces.
+static BACKTRACES_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether the errors capture a backtrace when `RUST_BACKTRACE` allows
+/// it. Enabled by default. Disabling them makes the errors behave as if
+/// `RUST_BACKTRACE` was `0`, including those of families with the
+/// `always_backtrace` option, but doesn't prevent explicit captures like
+/// `State::force_backtrace`.
+pub fn set_backtraces_enabled(enabled: bool) {
+    BACKTRACES_DISABLED.store(!enabled, Ordering::Relaxed);
+}
+
+/// Whether chaining captures a new backtrace even if the chained error
+/// already has one.
+static KEEP_ALL_BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether `chain_err` and `with_chain` capture a new backtrace when
+/// the chained error already has one. Disabled by default, so only the
+/// earliest backtrace is kept.
+///
+/// When enabled, all the backtraces are returned by
+/// `ChainedError::backtraces`, the earliest first, and `display` prints all
+/// of them.
+pub fn keep_all_backtraces(keep: bool) {
+    KEEP_ALL_BACKTRACES.store(keep, Ordering::Relaxed);
+}
+
+thread_local! {
+    /// The contexts pushed with `push_global_context`, shared with the errors
+    /// created while they are pushed.
+    static GLOBAL_CONTEXT: RefCell<Option<Arc<Vec<String>>>> = const { RefCell::new(None) };
+}
+
+/// Pushes a context which is recorded in all the errors created on the
+/// current thread, by `from_kind`, `with_chain` and the conversions and
+/// methods using them, until it is popped with `pop_global_context`.
+///
+/// The contexts are kept in a thread-local stack: errors created on other
+/// threads don't see them, and an error moved to another thread keeps the
+/// contexts of the thread it was created on. They are returned by
+/// `ChainedError::global_context` and printed by `display_detailed`.
+pub fn push_global_context(context: &str) {
+    GLOBAL_CONTEXT.with(|c| {
+        let mut c = c.borrow_mut();
+        let mut contexts = c.as_ref().map_or_else(Vec::new, |c| (**c).clone());
+        contexts.push(context.to_owned());
+        *c = Some(Arc::new(contexts));
+    })
+}
+
+/// Pops the last context pushed with `push_global_context` on the current
+/// thread, returning it.
+pub fn pop_global_context() -> Option<String> {
+    GLOBAL_CONTEXT.with(|c| {
+        let mut c = c.borrow_mut();
+        let mut contexts = c.as_ref().map_or_else(Vec::new, |c| (**c).clone());
+        let context = contexts.pop();
+        *c = if contexts.is_empty() { None } else { Some(Arc::new(contexts)) };
+        context
+    })
+}
+
+fn current_global_context() -> Option<Arc<Vec<String>>> {
+    GLOBAL_CONTEXT.with(|c| c.borrow().clone())
+}
+
+/// The cause replacing an error chain which was too long.
 #[derive(Debug)]
-pub struct Display<'a, T: 'a + ?Sized>(&'a T);
+struct CollapsedChain(String);
 
-impl<'a, T> fmt::Display for Display<'a, T>
-    where T: ChainedError
-{
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        try!(writeln!(fmt, "Error: {}", self.0));
+impl error::Error for CollapsedChain {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
 
-        for e in self.0.iter().skip(1) {
-            try!(writeln!(fmt, "Caused by: {}", e));
-        }
+impl fmt::Display for CollapsedChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
-        if let Some(backtrace) = self.0.backtrace() {
-            try!(writeln!(fmt, "{:?}", backtrace));
-        }
+/// Collapses the chain of `e` if chaining onto it would exceed the maximum
+/// chain depth.
+fn limit_chain_depth(e: Box<dyn error::Error + Send>) -> Option<Box<dyn error::Error + Send>> {
+    let max = MAX_CHAIN_DEPTH.load(Ordering::Relaxed);
+    if max == usize::MAX || ErrorChainIter(Some(&*e)).count() < max {
+        return Some(e);
+    }
+    if max <= 1 {
+        return None;
+    }
+    let messages: Vec<String> = ErrorChainIter(Some(&*e)).map(|e| e.to_string()).collect();
+    Some(Box::new(CollapsedChain(messages.join(": "))))
+}
 
-        Ok(())
+/// A cause shared by reference count between the clones of an error, see
+/// the `shared_cause` option.
+///
+/// The errors of the chain see through it: their `source` is the shared
+/// error itself.
+#[derive(Clone)]
+pub struct SharedCause(Arc<dyn error::Error + Send + Sync>);
+
+impl SharedCause {
+    /// Wraps `e` to be shared as the cause of an error.
+    pub fn new<E>(e: E) -> SharedCause
+        where E: error::Error + Send + Sync + 'static
+    {
+        SharedCause(Arc::new(e))
+    }
+
+    /// Returns the shared error.
+    pub fn get(&self) -> &(dyn error::Error + Send + Sync + 'static) {
+        &*self.0
+    }
+}
+
+impl fmt::Debug for SharedCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for SharedCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl error::Error for SharedCause {
+    #[allow(deprecated)]
+    fn description(&self) -> &str {
+        self.0.description()
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Returns the shared error if `e` is a `SharedCause`, or `e` otherwise.
+#[doc(hidden)]
+pub fn unwrap_shared_cause<'a>(e: &'a (dyn error::Error + Send + 'static)) -> &'a (dyn error::Error + 'static) {
+    match e.downcast_ref::<SharedCause>() {
+        Some(shared) => shared.get(),
+        None => e,
     }
 }
 
@@ -553,48 +2460,132 @@ impl<'a, T> fmt::Display for Display<'a, T>
 #[doc(hidden)]
 pub struct State {
     /// Next error in the error chain.
-    pub next_error: Option<Box<error::Error + Send>>,
+    pub next_error: Option<Box<dyn error::Error + Send>>,
     /// Backtrace for the current error.
     #[cfg(feature = "backtrace")]
-    pub backtrace: Option<Arc<Backtrace>>,
+    pub backtrace: Option<Arc<InternalBacktrace>>,
+    /// Backtraces captured when chaining, see `keep_all_backtraces`.
+    #[cfg(feature = "backtrace")]
+    pub later_backtraces: Vec<Arc<InternalBacktrace>>,
+    /// Locations of the calls which built the error chain, boxed as a slice
+    /// to keep the state small.
+    #[cfg(feature = "context-trace")]
+    pub context_trace: Box<[(&'static str, u32)]>,
+    /// Time at which the error was created, in nanoseconds since the Unix
+    /// epoch, which is smaller than a `SystemTime`.
+    #[cfg(feature = "timestamp")]
+    pub created_at: u64,
+    /// Global contexts at the creation of the error.
+    pub global_context: Option<Arc<Vec<String>>>,
+    /// Data which few errors have, boxed to keep the state small.
+    pub extras: Option<Box<StateExtras>>,
+}
+
+/// Data of the `State` which few errors have.
+#[derive(Debug, Clone, Default)]
+#[doc(hidden)]
+pub struct StateExtras {
+    /// Key-value pair given to `ResultExt::chain_err_kv`.
+    pub context_kv: Option<(&'static str, String)>,
+    /// Fields given to `ChainedError::with_field`.
+    #[cfg(feature = "serde")]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Returns the current time in nanoseconds since the Unix epoch, or `0` if
+/// the clock is set before it.
+#[cfg(feature = "timestamp")]
+fn timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos()))
 }
 
+// Only derivable when all the optional fields are disabled.
+#[allow(clippy::derivable_impls)]
 impl Default for State {
-    #[cfg(feature = "backtrace")]
     fn default() -> State {
         State {
             next_error: None,
+            #[cfg(feature = "backtrace")]
             backtrace: make_backtrace(),
+            #[cfg(feature = "backtrace")]
+            later_backtraces: Vec::new(),
+            #[cfg(feature = "context-trace")]
+            context_trace: Box::new([]),
+            #[cfg(feature = "timestamp")]
+            created_at: timestamp_now(),
+            global_context: current_global_context(),
+            extras: None,
         }
     }
-
-    #[cfg(not(feature = "backtrace"))]
-    fn default() -> State {
-        State { next_error: None }
-    }
 }
 
 impl State {
     /// Creates a new State type
-    #[cfg(feature = "backtrace")]
-    pub fn new<CE: ChainedError>(e: Box<error::Error + Send>) -> State {
-        let backtrace = CE::extract_backtrace(&*e).or_else(make_backtrace);
+    pub fn new<CE: ChainedError>(e: Box<dyn error::Error + Send>) -> State {
+        #[cfg(feature = "backtrace")]
+        let (backtrace, later_backtraces) = match CE::extract_backtrace(&*e) {
+            Some(backtrace) => {
+                let mut later = CE::extract_later_backtraces(&*e);
+                if KEEP_ALL_BACKTRACES.load(Ordering::Relaxed) {
+                    later.extend(make_backtrace());
+                }
+                (Some(backtrace), later)
+            }
+            None => (make_backtrace(), Vec::new()),
+        };
+        #[cfg(feature = "context-trace")]
+        let context_trace = CE::extract_context_trace(&*e).into_boxed_slice();
         State {
-            next_error: Some(e),
-            backtrace: backtrace,
+            next_error: limit_chain_depth(e),
+            #[cfg(feature = "backtrace")]
+            backtrace,
+            #[cfg(feature = "backtrace")]
+            later_backtraces,
+            #[cfg(feature = "context-trace")]
+            context_trace,
+            #[cfg(feature = "timestamp")]
+            created_at: timestamp_now(),
+            global_context: current_global_context(),
+            extras: None,
         }
     }
 
-    /// Creates a new State type
-    #[cfg(not(feature = "backtrace"))]
-    pub fn new<CE: ChainedError>(e: Box<error::Error + Send>) -> State {
-        State { next_error: Some(e) }
+    /// Clones the state for the `shared_cause` option. A `SharedCause` is
+    /// shared, a `CE` cause is cloned, and any other cause is replaced by an
+    /// error with the messages of its chain.
+    pub fn clone_shared<CE: ChainedError + Clone>(&self) -> State {
+        let next_error = self.next_error.as_ref().map(|e| -> Box<dyn error::Error + Send> {
+            if let Some(shared) = e.downcast_ref::<SharedCause>() {
+                Box::new(shared.clone())
+            } else if let Some(e) = e.downcast_ref::<CE>() {
+                Box::new(e.clone())
+            } else {
+                let messages: Vec<String> =
+                    ErrorChainIter(Some(&**e)).map(|e| e.to_string()).collect();
+                Box::new(CollapsedChain(messages.join(": ")))
+            }
+        });
+        State {
+            next_error,
+            #[cfg(feature = "backtrace")]
+            backtrace: self.backtrace.clone(),
+            #[cfg(feature = "backtrace")]
+            later_backtraces: self.later_backtraces.clone(),
+            #[cfg(feature = "context-trace")]
+            context_trace: self.context_trace.clone(),
+            #[cfg(feature = "timestamp")]
+            created_at: self.created_at,
+            global_context: self.global_context.clone(),
+            extras: self.extras.clone(),
+        }
     }
 
     /// Returns the inner backtrace if present.
     #[cfg(feature = "backtrace")]
     pub fn backtrace(&self) -> Option<&Backtrace> {
-        self.backtrace.as_ref().map(|v| &**v)
+        self.backtrace.as_ref().map(|v| &v.backtrace)
     }
 
     /// Returns the inner backtrace if present.
@@ -602,6 +2593,184 @@ impl State {
     pub fn backtrace(&self) -> Option<&Backtrace> {
         None
     }
+
+    /// Returns the backtrace followed by the backtraces captured when
+    /// chaining.
+    #[cfg(feature = "backtrace")]
+    pub fn backtraces(&self) -> Vec<&Backtrace> {
+        self.backtrace.iter().chain(&self.later_backtraces).map(|v| &v.backtrace).collect()
+    }
+
+    /// Returns the backtrace followed by the backtraces captured when
+    /// chaining.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn backtraces(&self) -> Vec<&Backtrace> {
+        Vec::new()
+    }
+
+    /// Captures a backtrace if there is none, and `RUST_BACKTRACE` allows it.
+    #[cfg(feature = "backtrace")]
+    pub fn ensure_backtrace(&mut self) {
+        if self.backtrace.is_none() {
+            self.backtrace = make_backtrace();
+        }
+    }
+
+    /// Captures a backtrace if there is none, and `RUST_BACKTRACE` allows it.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn ensure_backtrace(&mut self) {}
+
+    /// Applies the options of the `K` family to a new state: captures a
+    /// backtrace if there is none, `ALWAYS_BACKTRACE` is set, the
+    /// backtraces are not disabled and `RUST_BACKTRACE` is not `0`.
+    #[cfg(feature = "backtrace")]
+    pub fn with_options<K: ErrorChainOptions>(mut self) -> State {
+        if K::ALWAYS_BACKTRACE && !BACKTRACES_DISABLED.load(Ordering::Relaxed) {
+            match std::env::var_os("RUST_BACKTRACE") {
+                Some(ref val) if val == "0" => {}
+                _ => self.force_backtrace(),
+            }
+        }
+        self
+    }
+
+    /// Applies the options of the `K` family to a new state.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn with_options<K: ErrorChainOptions>(self) -> State {
+        self
+    }
+
+    /// Captures a backtrace if there is none, even if `RUST_BACKTRACE`
+    /// doesn't allow it.
+    #[cfg(feature = "backtrace")]
+    pub fn force_backtrace(&mut self) {
+        if self.backtrace.is_none() {
+            self.backtrace = Some(Arc::new(InternalBacktrace::new()));
+        }
+    }
+
+    /// Captures a backtrace if there is none, even if `RUST_BACKTRACE`
+    /// doesn't allow it.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn force_backtrace(&mut self) {}
+
+    /// Uses `backtrace` as the backtrace if there is none.
+    #[cfg(feature = "backtrace")]
+    pub fn attach_backtrace(&mut self, backtrace: Backtrace) {
+        if self.backtrace.is_none() {
+            self.backtrace = Some(Arc::new(InternalBacktrace::from_backtrace(backtrace)));
+        }
+    }
+
+    /// Uses `backtrace` as the backtrace if there is none.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn attach_backtrace(&mut self, _backtrace: Backtrace) {}
+
+    /// Returns the name and id of the thread the backtrace was captured on.
+    #[cfg(feature = "backtrace")]
+    pub fn thread(&self) -> Option<(Option<String>, ThreadId)> {
+        self.backtrace.as_ref().map(|v| (v.thread_name.clone(), v.thread_id))
+    }
+
+    /// Returns the name and id of the thread the backtrace was captured on.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn thread(&self) -> Option<(Option<String>, ThreadId)> {
+        None
+    }
+
+    /// Returns the time at which the error was created.
+    #[cfg(feature = "timestamp")]
+    pub fn created_at(&self) -> Option<SystemTime> {
+        Some(time::UNIX_EPOCH + time::Duration::from_nanos(self.created_at))
+    }
+
+    /// Returns the time at which the error was created.
+    #[cfg(not(feature = "timestamp"))]
+    pub fn created_at(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Returns the locations of the calls which built the error chain.
+    #[cfg(feature = "context-trace")]
+    pub fn context_trace(&self) -> &[(&'static str, u32)] {
+        &self.context_trace
+    }
+
+    /// Returns the locations of the calls which built the error chain.
+    #[cfg(not(feature = "context-trace"))]
+    pub fn context_trace(&self) -> &[(&'static str, u32)] {
+        &[]
+    }
+
+    /// Creates a state from a next error and a backtrace, without capturing
+    /// a backtrace.
+    pub fn from_parts(next_error: Option<Box<dyn error::Error + Send>>,
+                      backtrace: Option<Backtrace>)
+                      -> State {
+        #[cfg(not(feature = "backtrace"))]
+        let _ = backtrace;
+        State {
+            next_error,
+            #[cfg(feature = "backtrace")]
+            backtrace: backtrace.map(|b| Arc::new(InternalBacktrace::from_backtrace(b))),
+            #[cfg(feature = "backtrace")]
+            later_backtraces: Vec::new(),
+            #[cfg(feature = "context-trace")]
+            context_trace: Box::new([]),
+            #[cfg(feature = "timestamp")]
+            created_at: timestamp_now(),
+            global_context: current_global_context(),
+            extras: None,
+        }
+    }
+
+    /// Returns the next error and the backtrace.
+    #[cfg(feature = "backtrace")]
+    pub fn into_parts(self) -> (Option<Box<dyn error::Error + Send>>, Option<Backtrace>) {
+        let backtrace = self.backtrace.map(|b| match Arc::try_unwrap(b) {
+            Ok(b) => b.backtrace,
+            Err(b) => b.backtrace.clone(),
+        });
+        (self.next_error, backtrace)
+    }
+
+    /// Returns the next error and the backtrace.
+    #[cfg(not(feature = "backtrace"))]
+    pub fn into_parts(self) -> (Option<Box<dyn error::Error + Send>>, Option<Backtrace>) {
+        (self.next_error, None)
+    }
+
+    /// Removes the next error and the backtraces.
+    pub fn reset_cause_and_backtrace(&mut self) {
+        self.next_error = None;
+        #[cfg(feature = "backtrace")]
+        {
+            self.backtrace = None;
+            self.later_backtraces.clear();
+        }
+    }
+
+    /// Returns the extras of the state, creating them if there are none.
+    pub fn extras_mut(&mut self) -> &mut StateExtras {
+        self.extras.get_or_insert_with(Default::default)
+    }
+
+    /// Returns the global contexts at the creation of the error.
+    pub fn global_context(&self) -> &[String] {
+        self.global_context.as_ref().map_or(&[], |c| &c[..])
+    }
+
+    /// Records a location in the context trace.
+    #[cfg(feature = "context-trace")]
+    pub fn push_context(&mut self, file: &'static str, line: u32) {
+        let mut trace = ::std::mem::replace(&mut self.context_trace, Box::new([])).into_vec();
+        trace.push((file, line));
+        self.context_trace = trace.into_boxed_slice();
+    }
+
+    /// Records a location in the context trace.
+    #[cfg(not(feature = "context-trace"))]
+    pub fn push_context(&mut self, _file: &'static str, _line: u32) {}
 }
 
 /// Exits a function early with an error
@@ -631,6 +2800,30 @@ impl State {
 /// # }
 /// ```
 ///
+/// The formatted string is converted into the returned error with
+/// `From<String>`. For an error generated by `error_chain!`, this builds a
+/// `Msg` error, unless the family sets the `bail_kind(...)` option to a
+/// variant taking a `String`:
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # fn main() { }
+/// error_chain! {
+///     errors {
+///         Failed(s: String)
+///     }
+///
+///     options {
+///         bail_kind(Failed);
+///     }
+/// }
+///
+/// fn foo(n: u8) -> Result<()> {
+///     // Fails with `ErrorKind::Failed("bad number: 3")`
+///     bail!("bad number: {}", n);
+/// }
+/// ```
+///
 /// # Examples
 ///
 /// Bailing on a custom error:
@@ -680,6 +2873,99 @@ macro_rules! bail {
     };
 }
 
+/// Exits a function early with an error, like `bail!`, recording the
+/// location of the call in the context trace of the error.
+///
+/// The location is only recorded with the `context-trace` feature, but the
+/// returned error must always be an error generated by `error_chain!`.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # fn main() {}
+/// error_chain! { }
+///
+/// fn foo(n: u8) -> Result<()> {
+///     bail_traced!("bad number: {}", n);
+/// }
+/// ```
+#[macro_export]
+macro_rules! bail_traced {
+    ($e:expr) => {
+        return Err($crate::with_context($e.into(), file!(), line!()));
+    };
+    ($fmt:expr, $($arg:tt)+) => {
+        return Err($crate::with_context(format!($fmt, $($arg)+).into(), file!(), line!()));
+    };
+}
+
+/// Exits a function early with an existing error, chained onto a new error
+/// as by `chain_err`.
+///
+/// `bail_chain!(err, kind)` is equivalent to
+/// `return Err(err.chain_err(|| kind))`: `err` must be an error generated by
+/// `error_chain!`, and becomes the cause of the returned error. This is
+/// unlike `bail!`, which creates a new error without a cause. The new error
+/// can be a kind or a formatted string, as with `bail!`.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # fn main() {}
+/// error_chain! { }
+///
+/// fn foo() -> Result<()> {
+///     if let Err(e) = bar() {
+///         bail_chain!(e, "bar failed for {}", "foo");
+///     }
+///
+///     Ok(())
+/// }
+///
+/// # fn bar() -> Result<()> { Ok(()) }
+/// ```
+#[macro_export]
+macro_rules! bail_chain {
+    ($err:expr, $e:expr) => {
+        return Err($err.chain_err(|| $e));
+    };
+    ($err:expr, $fmt:expr, $($arg:tt)+) => {
+        return Err($err.chain_err(|| format!($fmt, $($arg)+)));
+    };
+}
+
+/// Exits a function early with an error message prefixed by the path of
+/// the enclosing function.
+///
+/// The path is found with `std::any::type_name`, so it is a best-effort
+/// diagnostic aid: inside closures it contains `{{closure}}` segments.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # error_chain! { }
+/// # fn main() { }
+/// fn foo(n: u8) -> Result<()> {
+///     // Fails with "rust_out::foo: bad number: 3"
+///     bail_fn!("bad number: {}", n);
+/// }
+/// ```
+#[macro_export]
+macro_rules! bail_fn {
+    ($($arg:tt)+) => {{
+        fn __bail_fn() {}
+        bail!("{}: {}", $crate::function_name(__bail_fn), format!($($arg)+));
+    }};
+}
+
+/// Returns the path of the function containing the function `f`. Used by
+/// `bail_fn!`.
+#[doc(hidden)]
+pub fn function_name<F>(_f: F) -> &'static str {
+    let name = std::any::type_name::<F>();
+    match name.rfind("::") {
+        Some(i) => &name[..i],
+        None => name,
+    }
+}
+
 /// Exits a function early with an error if the condition is not satisfied
 ///
 /// The `ensure!` macro is a convenience helper that provides a way to exit
@@ -702,6 +2988,24 @@ macro_rules! bail {
 /// ```
 ///
 /// See documentation for `bail!` macro for further details.
+///
+/// `ensure!(condition, kind, cause)` instead fails with
+/// `Error::with_chain(cause, kind)`, so that `cause` is kept as the cause of
+/// the returned error. Since a string literal as the second argument is
+/// taken as a format string, the kind must not be a string literal.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// # fn main() {}
+/// error_chain! {
+///     errors { MissingConfig }
+/// }
+///
+/// fn load(config: Option<&str>, err: std::io::Error) -> Result<()> {
+///     ensure!(config.is_some(), ErrorKind::MissingConfig, err);
+///     Ok(())
+/// }
+/// ```
 #[macro_export]
 macro_rules! ensure {
     ($cond:expr, $e:expr) => {
@@ -709,6 +3013,16 @@ macro_rules! ensure {
             bail!($e);
         }
     };
+    ($cond:expr, $fmt:literal, $($arg:tt)+) => {
+        if !($cond) {
+            bail!($fmt, $($arg)+);
+        }
+    };
+    ($cond:expr, $kind:expr, $cause:expr) => {
+        if !($cond) {
+            return Err($crate::ChainedError::with_chain($cause, $kind));
+        }
+    };
     ($cond:expr, $fmt:expr, $($arg:tt)+) => {
         if !($cond) {
             bail!($fmt, $($arg)+);
@@ -1,8 +1,10 @@
 // From https://github.com/tailhook/quick-error
 // Changes:
-//   - replace `impl Error` by `impl Item::description`
+//   - replace `impl Error` by `impl Item::description`, which `impl Error` forwards to
 //   - $imeta
 
+/// Internal macro used to define the `ErrorKind`.
+#[doc(hidden)]
 #[macro_export]
 macro_rules! quick_error {
     (   $(#[$meta:meta])*
@@ -19,6 +21,13 @@ macro_rules! quick_error {
             items [] buf []
             queue [ $($chunks)* ]);
     };
+    (   $(#[$meta:meta])*
+        pub enum $name:ident < $lt:lifetime > { $($chunks:tt)* }
+    ) => {
+        quick_error!(SORT [pub enum $name < $lt > $(#[$meta])* ]
+            items [] buf []
+            queue [ $($chunks)* ]);
+    };
     // Queue is empty, can do the work
     (SORT [enum $name:ident $( #[$meta:meta] )*]
         items [$($( #[$imeta:meta] )*
@@ -32,7 +41,7 @@ macro_rules! quick_error {
             queue [$($( #[$imeta] )*
                       => $iitem: $imode [$( $ivar: $ityp ),*] )*]
         );
-        quick_error!(IMPLEMENTATIONS $name {$(
+        quick_error!(IMPLEMENTATIONS $name [] [] {$(
            $iitem: $imode [$(#[$imeta])*] [$( $ivar: $ityp ),*] {$( $ifuncs )*}
            )*});
         $(
@@ -51,7 +60,26 @@ macro_rules! quick_error {
             queue [$($( #[$imeta] )*
                       => $iitem: $imode [$( $ivar: $ityp ),*] )*]
         );
-        quick_error!(IMPLEMENTATIONS $name {$(
+        quick_error!(IMPLEMENTATIONS $name [] [] {$(
+           $iitem: $imode [$(#[$imeta])*] [$( $ivar: $ityp ),*] {$( $ifuncs )*}
+           )*});
+        $(
+            quick_error!(ERROR_CHECK $imode $($ifuncs)*);
+        )*
+    };
+ (SORT [pub enum $name:ident < $lt:lifetime > $( #[$meta:meta] )*]
+        items [$($( #[$imeta:meta] )*
+                  => $iitem:ident: $imode:tt [$( $ivar:ident: $ityp:ty ),*]
+                                {$( $ifuncs:tt )*} )* ]
+        buf [ ]
+        queue [ ]
+    ) => {
+        quick_error!(ENUM_DEFINITION [pub enum $name < $lt > $( #[$meta] )*]
+            body []
+            queue [$($( #[$imeta] )*
+                      => $iitem: $imode [$( $ivar: $ityp ),*] )*]
+        );
+        quick_error!(IMPLEMENTATIONS $name [< $lt >] [< $lt >] {$(
            $iitem: $imode [$(#[$imeta])*] [$( $ivar: $ityp ),*] {$( $ifuncs )*}
            )*});
         $(
@@ -202,6 +230,20 @@ macro_rules! quick_error {
             )*
         }
     };
+    // Public enum with a lifetime (Queue Empty)
+    (ENUM_DEFINITION [pub enum $name:ident < $lt:lifetime > $( #[$meta:meta] )*]
+        body [$($( #[$imeta:meta] )*
+            => $iitem:ident ($(($( $ttyp:ty ),+))*) {$({$( $svar:ident: $styp:ty ),*})*} )* ]
+        queue [ ]
+    ) => {
+        $(#[$meta])*
+        pub enum $name < $lt > {
+            $(
+                $(#[$imeta])*
+                $iitem $(($( $ttyp ),*))* $({$( $svar: $styp ),*})*,
+            )*
+        }
+    };
     // Private enum (Queue Empty)
     (ENUM_DEFINITION [enum $name:ident $( #[$meta:meta] )*]
         body [$($( #[$imeta:meta] )*
@@ -256,12 +298,12 @@ macro_rules! quick_error {
         );
     };
     (IMPLEMENTATIONS
-        $name:ident {$(
+        $name:ident [$( $gen:tt )*] $generics:tt {$(
             $item:ident: $imode:tt [$(#[$imeta:meta])*] [$( $var:ident: $typ:ty ),*] {$( $funcs:tt )*}
         )*}
     ) => {
-        #[allow(unused)]
-        impl ::std::fmt::Display for $name {
+        #[allow(unused, deprecated)]
+        impl $( $gen )* ::std::fmt::Display for $name $( $gen )* {
             fn fmt(&self, fmt: &mut ::std::fmt::Formatter)
                 -> ::std::fmt::Result
             {
@@ -275,17 +317,30 @@ macro_rules! quick_error {
                                 $name $item: $imode
                                 {$( $funcs )*});
 
+                            if <Self as $crate::ErrorChainOptions>::DISPLAY_WITH_CODE {
+                                if let Some(code) = self.code() {
+                                    write!(fmt, "[{}] ", code)?;
+                                }
+                            }
                             display_fn(self, fmt)
                         }
                     )*
                 }
             }
         }
-        /*#[allow(unused)]
-        impl ::std::error::Error for $name {
+        #[allow(unused, deprecated)]
+        impl $( $gen )* ::std::error::Error for $name $( $gen )* {
             fn description(&self) -> &str {
+                Self::description(self)
+            }
+        }
+        #[allow(unused, deprecated)]
+        impl $( $gen )* $name $( $gen )* {
+            /// A string describing the error kind.
+            pub fn description(&self) -> &str {
                 match *self {
                     $(
+                        $(#[$imeta])*
                         quick_error!(ITEM_PATTERN
                             $name $item: $imode [$( ref $var ),*]
                         ) => {
@@ -296,41 +351,163 @@ macro_rules! quick_error {
                     )*
                 }
             }
-            fn cause(&self) -> Option<&::std::error::Error> {
+
+            /// The code of the error kind, if one was declared with `code(...)`.
+            pub fn code(&self) -> Option<i32> {
                 match *self {
                     $(
+                        $(#[$imeta])*
                         quick_error!(ITEM_PATTERN
                             $name $item: $imode [$( ref $var ),*]
                         ) => {
-                            quick_error!(FIND_CAUSE_IMPL
-                                $item: $imode [$( $var ),*]
-                                {$( $funcs )*})
+                            quick_error!(FIND_CODE_IMPL
+                                $item: $imode {$( $funcs )*})
                         }
                     )*
                 }
             }
-        }*/
-        #[allow(unused)]
-        impl $name {
-            /// A string describing the error kind.
-            pub fn description(&self) -> &str {
+
+            /// The description of the error kind if it was declared as a
+            /// string literal, or the name of the variant otherwise.
+            pub fn description_static(&self) -> &'static str {
                 match *self {
                     $(
                         $(#[$imeta])*
                         quick_error!(ITEM_PATTERN
                             $name $item: $imode [$( ref $var ),*]
                         ) => {
-                            quick_error!(FIND_DESCRIPTION_IMPL
-                                $item: $imode self fmt [$( $var ),*]
-                                {$( $funcs )*})
+                            quick_error!(FIND_DESCRIPTION_STATIC_IMPL
+                                $item: $imode {$( $funcs )*})
+                        }
+                    )*
+                }
+            }
+
+            /// The name and static description of every variant, in
+            /// declaration order.
+            #[allow(unused_doc_comments)]
+            pub const DESCRIPTIONS: &'static [(&'static str, &'static str)] = &[
+                $(
+                    $(#[$imeta])*
+                    (stringify!($item), quick_error!(FIND_DESCRIPTION_STATIC_IMPL
+                        $item: $imode {$( $funcs )*})),
+                )*
+            ];
+
+            /// Whether the error kind was declared `retriable`.
+            pub fn is_retriable(&self) -> bool {
+                match *self {
+                    $(
+                        $(#[$imeta])*
+                        quick_error!(ITEM_PATTERN
+                            $name $item: $imode [$( ref $var ),*]
+                        ) => {
+                            quick_error!(FIND_RETRIABLE_IMPL
+                                $item: $imode {$( $funcs )*})
+                        }
+                    )*
+                }
+            }
+
+            /// The message of the error kind declared with
+            /// `public_display(...)`, or a generic message otherwise.
+            ///
+            /// Unlike the `Display` message, it is meant to be shown to the
+            /// users, so it shouldn't contain sensitive details.
+            pub fn public_display(&self) -> String {
+                match *self {
+                    $(
+                        $(#[$imeta])*
+                        quick_error!(ITEM_PATTERN
+                            $name $item: $imode [$( ref $var ),*]
+                        ) => {
+                            quick_error!(FIND_PUBLIC_DISPLAY_IMPL
+                                $item: $imode {$( $funcs )*})
+                        }
+                    )*
+                }
+            }
+
+            /// The name of the variant.
+            pub fn variant_name(&self) -> &'static str {
+                match *self {
+                    $(
+                        $(#[$imeta])*
+                        quick_error!(ITEM_PATTERN
+                            $name $item: $imode [$( ref $var ),*]
+                        ) => stringify!($item),
+                    )*
+                }
+            }
+
+            /// Formats the error kind like `Display`, except for the variants
+            /// declared with `display_with_cause(...)`, which are given
+            /// `cause`. This is used by the `Display` implementation of the
+            /// `Error`, with its cause.
+            pub fn fmt_with_cause(&self, fmt: &mut ::std::fmt::Formatter,
+                                  cause: Option<&dyn (::std::error::Error)>)
+                -> ::std::fmt::Result
+            {
+                match *self {
+                    $(
+                        $(#[$imeta])*
+                        quick_error!(ITEM_PATTERN
+                            $name $item: $imode [$( ref $var ),*]
+                        ) => {
+                            quick_error!(FIND_DISPLAY_WITH_CAUSE_IMPL
+                                self fmt cause [$( $var ),*] {$( $funcs )*})
                         }
                     )*
                 }
             }
+
+            /// The description of the error kind if one was declared with
+            /// `description(...)`, or its display message otherwise.
+            ///
+            /// Unlike `description`, this can include the fields of the
+            /// variant.
+            pub fn detailed_description(&self) -> String {
+                let has_description = match *self {
+                    $(
+                        $(#[$imeta])*
+                        quick_error!(ITEM_PATTERN
+                            $name $item: $imode [$( ref $var ),*]
+                        ) => {
+                            quick_error!(FIND_HAS_DESCRIPTION_IMPL
+                                $item: $imode {$( $funcs )*})
+                        }
+                    )*
+                };
+                if has_description {
+                    self.description().to_owned()
+                } else {
+                    self.to_string()
+                }
+            }
         }
+        quick_error!(IF_ANY_CODE {
+            /// Converts a code declared with `code(...)` back into its variant.
+            /// Only variants without fields can be converted, any other code is
+            /// given back as the error.
+            #[allow(unused, deprecated, unknown_lints, unused_doc_comments)]
+            impl $( $gen )* ::std::convert::TryFrom<i64> for $name $( $gen )* {
+                type Error = i64;
+
+                fn try_from(code: i64) -> ::std::result::Result<Self, i64> {
+                    $(
+                        $(#[$imeta])*
+                        {
+                            quick_error!(FIND_TRY_FROM_CODE_IMPL
+                                $name $item: $imode code {$( $funcs )*});
+                        }
+                    )*
+                    Err(code)
+                }
+            }
+        } { $( $( $funcs )* )* });
         $(
             quick_error!(FIND_FROM_IMPL
-                $name $item: $imode [$( $var:$typ ),*]
+                $name $generics $item: $imode [$( $var:$typ ),*]
                 {$( $funcs )*});
         )*
     };
@@ -385,6 +562,151 @@ macro_rules! quick_error {
     ) => {
         stringify!($item)
     };
+    (FIND_CODE_IMPL $item:ident: $imode:tt
+        { code($expr:expr) $( $tail:tt )*}
+    ) => {
+        Some($expr)
+    };
+    (FIND_CODE_IMPL $item:ident: $imode:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_CODE_IMPL
+            $item: $imode {$( $tail )*})
+    };
+    (FIND_CODE_IMPL $item:ident: $imode:tt
+        { }
+    ) => {
+        None
+    };
+    (FIND_DESCRIPTION_STATIC_IMPL $item:ident: $imode:tt
+        { description($lit:literal) $( $tail:tt )*}
+    ) => {
+        $lit
+    };
+    (FIND_DESCRIPTION_STATIC_IMPL $item:ident: $imode:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_DESCRIPTION_STATIC_IMPL
+            $item: $imode {$( $tail )*})
+    };
+    (FIND_DESCRIPTION_STATIC_IMPL $item:ident: $imode:tt
+        { }
+    ) => {
+        stringify!($item)
+    };
+    // Expands to the items if a `code(...)` is among the funcs of all the
+    // variants. The funcs are mostly an ident followed by parentheses, so
+    // they are skipped in pairs to keep the recursion shallow.
+    (IF_ANY_CODE { $( $items:tt )* } { code($expr:expr) $( $tail:tt )* }) => {
+        $( $items )*
+    };
+    (IF_ANY_CODE $items:tt { $func:ident ($( $args:tt )*) $( $tail:tt )* }) => {
+        quick_error!(IF_ANY_CODE $items { $( $tail )* });
+    };
+    (IF_ANY_CODE $items:tt { $t:tt $( $tail:tt )* }) => {
+        quick_error!(IF_ANY_CODE $items { $( $tail )* });
+    };
+    (IF_ANY_CODE $items:tt { }) => {};
+    (FIND_TRY_FROM_CODE_IMPL $name:ident $item:ident: UNIT $code:ident
+        { code($expr:expr) $( $tail:tt )*}
+    ) => {
+        if $code == ($expr) as i64 {
+            return Ok($name::$item);
+        }
+    };
+    (FIND_TRY_FROM_CODE_IMPL $name:ident $item:ident: $imode:tt $code:ident
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_TRY_FROM_CODE_IMPL
+            $name $item: $imode $code {$( $tail )*})
+    };
+    (FIND_TRY_FROM_CODE_IMPL $name:ident $item:ident: $imode:tt $code:ident
+        { }
+    ) => {};
+    (FIND_PUBLIC_DISPLAY_IMPL $item:ident: $imode:tt
+        { public_display($self_:tt) -> ($( $exprs:tt )*) $( $tail:tt )*}
+    ) => {
+        {
+            let quick_error!(IDENT $self_) = self;
+            format!($( $exprs )*)
+        }
+    };
+    (FIND_PUBLIC_DISPLAY_IMPL $item:ident: $imode:tt
+        { public_display($pattern:expr) $( $tail:tt )*}
+    ) => {
+        format!($pattern)
+    };
+    (FIND_PUBLIC_DISPLAY_IMPL $item:ident: $imode:tt
+        { public_display($pattern:expr, $( $exprs:tt )*) $( $tail:tt )*}
+    ) => {
+        format!($pattern, $( $exprs )*)
+    };
+    (FIND_PUBLIC_DISPLAY_IMPL $item:ident: $imode:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_PUBLIC_DISPLAY_IMPL
+            $item: $imode {$( $tail )*})
+    };
+    (FIND_PUBLIC_DISPLAY_IMPL $item:ident: $imode:tt
+        { }
+    ) => {
+        String::from("an error occurred")
+    };
+    (FIND_DISPLAY_WITH_CAUSE_IMPL $self_:tt $fmt:ident $cause:ident [$( $var:ident ),*]
+        { display_with_cause($display:expr) $( $tail:tt )*}
+    ) => {
+        {
+            if <Self as $crate::ErrorChainOptions>::DISPLAY_WITH_CODE {
+                if let Some(code) = $self_.code() {
+                    write!($fmt, "[{}] ", code)?;
+                }
+            }
+            $crate::display_with_cause($display, $fmt, ($( $var ),*), $cause)
+        }
+    };
+    (FIND_DISPLAY_WITH_CAUSE_IMPL $self_:tt $fmt:ident $cause:ident $vars:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_DISPLAY_WITH_CAUSE_IMPL
+            $self_ $fmt $cause $vars {$( $tail )*})
+    };
+    (FIND_DISPLAY_WITH_CAUSE_IMPL $self_:tt $fmt:ident $cause:ident $vars:tt
+        { }
+    ) => {
+        ::std::fmt::Display::fmt($self_, $fmt)
+    };
+    (FIND_RETRIABLE_IMPL $item:ident: $imode:tt
+        { retriable $( $tail:tt )*}
+    ) => {
+        true
+    };
+    (FIND_RETRIABLE_IMPL $item:ident: $imode:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_RETRIABLE_IMPL
+            $item: $imode {$( $tail )*})
+    };
+    (FIND_RETRIABLE_IMPL $item:ident: $imode:tt
+        { }
+    ) => {
+        false
+    };
+    (FIND_HAS_DESCRIPTION_IMPL $item:ident: $imode:tt
+        { description($expr:expr) $( $tail:tt )*}
+    ) => {
+        true
+    };
+    (FIND_HAS_DESCRIPTION_IMPL $item:ident: $imode:tt
+        { $t:tt $( $tail:tt )*}
+    ) => {
+        quick_error!(FIND_HAS_DESCRIPTION_IMPL
+            $item: $imode {$( $tail )*})
+    };
+    (FIND_HAS_DESCRIPTION_IMPL $item:ident: $imode:tt
+        { }
+    ) => {
+        false
+    };
     (FIND_CAUSE_IMPL $item:ident: $imode:tt
         [$( $var:ident ),*]
         { cause($expr:expr) $( $tail:tt )*}
@@ -405,7 +727,20 @@ macro_rules! quick_error {
     ) => {
         None
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: $imode:tt
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: $imode:tt
+        [$var:ident: $typ:ty]
+        { from() $( $tail:tt )*}
+    ) => {
+        impl $( $gen )* From<$typ> for $name $( $gen )* {
+            fn from($var: $typ) -> Self {
+                $name::$item($var)
+            }
+        }
+        quick_error!(FIND_FROM_IMPL
+            $name [$( $gen )*] $item: $imode [$var:$typ]
+            {$( $tail )*});
+    };
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: $imode:tt
         [$( $var:ident: $typ:ty ),*]
         { from() $( $tail:tt )*}
     ) => {
@@ -417,60 +752,60 @@ macro_rules! quick_error {
             }
         )*
         quick_error!(FIND_FROM_IMPL
-            $name $item: $imode [$( $var:$typ ),*]
+            $name [$( $gen )*] $item: $imode [$( $var:$typ ),*]
             {$( $tail )*});
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: UNIT
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: UNIT
         [ ]
         { from($ftyp:ty) $( $tail:tt )*}
     ) => {
-        impl From<$ftyp> for $name {
-            fn from(_discarded_error: $ftyp) -> $name {
+        impl $( $gen )* From<$ftyp> for $name $( $gen )* {
+            fn from(_discarded_error: $ftyp) -> Self {
                 $name::$item
             }
         }
         quick_error!(FIND_FROM_IMPL
-            $name $item: UNIT [  ]
+            $name [$( $gen )*] $item: UNIT [  ]
             {$( $tail )*});
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: TUPLE
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: TUPLE
         [$( $var:ident: $typ:ty ),*]
         { from($fvar:ident: $ftyp:ty) -> ($( $texpr:expr ),*) $( $tail:tt )*}
     ) => {
-        impl From<$ftyp> for $name {
-            fn from($fvar: $ftyp) -> $name {
+        impl $( $gen )* From<$ftyp> for $name $( $gen )* {
+            fn from($fvar: $ftyp) -> Self {
                 $name::$item($( $texpr ),*)
             }
         }
         quick_error!(FIND_FROM_IMPL
-            $name $item: TUPLE [$( $var:$typ ),*]
+            $name [$( $gen )*] $item: TUPLE [$( $var:$typ ),*]
             { $($tail)* });
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: STRUCT
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: STRUCT
         [$( $var:ident: $typ:ty ),*]
         { from($fvar:ident: $ftyp:ty) -> {$( $tvar:ident: $texpr:expr ),*} $( $tail:tt )*}
     ) => {
-        impl From<$ftyp> for $name {
-            fn from($fvar: $ftyp) -> $name {
+        impl $( $gen )* From<$ftyp> for $name $( $gen )* {
+            fn from($fvar: $ftyp) -> Self {
                 $name::$item {
                     $( $tvar: $texpr ),*
                 }
             }
         }
         quick_error!(FIND_FROM_IMPL
-            $name $item: STRUCT [$( $var:$typ ),*]
+            $name [$( $gen )*] $item: STRUCT [$( $var:$typ ),*]
             { $($tail)* });
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: $imode:tt
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: $imode:tt
         [$( $var:ident: $typ:ty ),*]
         { $t:tt $( $tail:tt )*}
     ) => {
         quick_error!(FIND_FROM_IMPL
-            $name $item: $imode [$( $var:$typ ),*]
+            $name [$( $gen )*] $item: $imode [$( $var:$typ ),*]
             {$( $tail )*}
         );
     };
-    (FIND_FROM_IMPL $name:ident $item:ident: $imode:tt
+    (FIND_FROM_IMPL $name:ident [$( $gen:tt )*] $item:ident: $imode:tt
         [$( $var:ident: $typ:ty ),*]
         { }
     ) => {
@@ -511,8 +846,20 @@ macro_rules! quick_error {
     => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
     (ERROR_CHECK $imode:tt display($pattern: expr, $( $exprs:tt )*) $( $tail:tt )*)
     => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt display_with_cause($display:expr) $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt public_display($self_:tt) -> ($( $exprs:tt )*) $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt public_display($pattern: expr) $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt public_display($pattern: expr, $( $exprs:tt )*) $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
     (ERROR_CHECK $imode:tt description($expr:expr) $( $tail:tt )*)
     => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt code($expr:expr) $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
+    (ERROR_CHECK $imode:tt retriable $( $tail:tt )*)
+    => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
     (ERROR_CHECK $imode:tt cause($expr:expr) $($tail:tt)*)
     => { quick_error!(ERROR_CHECK_COMMA $imode $($tail)*); };
     (ERROR_CHECK $imode:tt from() $($tail:tt)*)
@@ -1,3 +1,8 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use ChainedError;
+
 /// Convenient wrapper to be able to use `try!` and such in the main. You can
 /// use it with a separated function:
 ///
@@ -25,7 +30,9 @@
 /// # }
 /// ```
 ///
-/// You can also set the exit value of the process by returning a type that implements [`ExitCode`](trait.ExitCode.html):
+/// You can also set the exit value of the process by returning a type that
+/// implements [`IntoExitCode`](trait.IntoExitCode.html), such as `i32` or
+/// [`ExitCode`](struct.ExitCode.html):
 ///
 /// ```
 /// # #[macro_use] extern crate error_chain;
@@ -34,44 +41,94 @@
 /// quick_main!(run);
 /// # }
 ///
-/// fn run() -> Result<i32> {
-///     Err("error".into())
+/// fn run() -> Result<error_chain::ExitCode> {
+///     Ok(error_chain::ExitCode(3))
 /// }
 /// ```
+///
+/// On unix, where the exit status is a single byte, codes outside `0..=255`
+/// are truncated to their low byte as shells do, so `-1` exits with `255`
+/// and `300` with `44`. A code whose low byte is `0`, like `256`, exits with
+/// `1` instead, so that a nonzero code never reports success.
 #[macro_export]
 macro_rules! quick_main {
     ($main:expr) => {
         fn main() {
-            use ::std::io::Write;
-
-            ::std::process::exit(match $main() {
-                Ok(ret) => $crate::ExitCode::code(ret),
-                Err(ref e) => {
-                    write!(&mut ::std::io::stderr(), "{}", $crate::ChainedError::display(e))
-                        .expect("Error writing to stderr");
-
-                    1
-                }
-            });
+            ::std::process::exit($crate::exit_code($main()));
         }
     };
 }
 
 /// Represents a value that can be used as the exit status of the process.
 /// See [`quick_main!`](macro.quick_main.html).
-pub trait ExitCode {
+pub trait IntoExitCode {
     /// Returns the value to use as the exit status.
     fn code(self) -> i32;
 }
 
-impl ExitCode for i32 {
+impl IntoExitCode for i32 {
     fn code(self) -> i32 {
         self
     }
 }
 
-impl ExitCode for () {
+impl IntoExitCode for () {
     fn code(self) -> i32 {
         0
     }
 }
+
+/// An exit status to return from the function run by
+/// [`quick_main!`](macro.quick_main.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExitCode(pub i32);
+
+impl From<i32> for ExitCode {
+    fn from(code: i32) -> ExitCode {
+        ExitCode(code)
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl IntoExitCode for ExitCode {
+    fn code(self) -> i32 {
+        self.0
+    }
+}
+
+/// Returns the exit status `quick_main!` uses for `result`, writing the
+/// error chain to stderr if it is an `Err`.
+#[doc(hidden)]
+pub fn exit_code<T, E>(result: Result<T, E>) -> i32
+    where T: IntoExitCode,
+          E: ChainedError
+{
+    let code = match result {
+        Ok(ret) => ret.code(),
+        Err(ref e) => {
+            write!(&mut io::stderr(), "{}", e.display())
+                .expect("Error writing to stderr");
+
+            1
+        }
+    };
+    clamp_exit_code(code)
+}
+
+#[cfg(unix)]
+fn clamp_exit_code(code: i32) -> i32 {
+    match code & 0xff {
+        0 if code != 0 => 1,
+        truncated => truncated,
+    }
+}
+
+#[cfg(not(unix))]
+fn clamp_exit_code(code: i32) -> i32 {
+    code
+}
@@ -0,0 +1,83 @@
+use std::fmt::Write;
+
+use AnyChained;
+
+/// Renders an error chain into a `String`, see `ChainedError::render_with`.
+///
+/// This allows choosing the rendering of errors depending on where they are
+/// shown, for instance in logs or in a user interface, without depending on
+/// their family.
+///
+/// ```
+/// # #[macro_use] extern crate error_chain;
+/// use error_chain::{AnyChained, ChainRenderer, ChainedError};
+///
+/// error_chain! {}
+///
+/// struct Shouting;
+///
+/// impl ChainRenderer for Shouting {
+///     fn render(&self, err: &dyn AnyChained) -> String {
+///         let messages: Vec<String> = err.any_iter().map(|e| e.to_string().to_uppercase()).collect();
+///         messages.join("! ")
+///     }
+/// }
+///
+/// # fn main() {
+/// let err = Error::from("root").chain_err(|| "top");
+/// assert_eq!(err.render_with(&Shouting), "TOP! ROOT");
+/// # }
+/// ```
+pub trait ChainRenderer {
+    /// Renders `err` and its causes.
+    fn render(&self, err: &dyn AnyChained) -> String;
+}
+
+/// Renders the messages of the chain on a single line, separated by `: `,
+/// like `ChainedError::display_chain_inline`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineRenderer;
+
+impl ChainRenderer for InlineRenderer {
+    fn render(&self, err: &dyn AnyChained) -> String {
+        let messages: Vec<String> = err.any_iter().map(|e| e.to_string()).collect();
+        messages.join(": ")
+    }
+}
+
+/// Renders each cause on its own line, indented by two more spaces than the
+/// error it caused, like `ChainedError::display_chain_indented` without the
+/// backtrace.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeRenderer;
+
+impl ChainRenderer for TreeRenderer {
+    fn render(&self, err: &dyn AnyChained) -> String {
+        let mut out = String::new();
+        let mut indent = String::new();
+        for (i, e) in err.any_iter().enumerate() {
+            if i == 0 {
+                let _ = writeln!(out, "Error: {}", e);
+            } else {
+                indent.push_str("  ");
+                let _ = writeln!(out, "{}caused by: {}", indent, e);
+            }
+        }
+        out
+    }
+}
+
+/// Renders each error of the chain on its own line, prefixed with its
+/// depth in the chain, starting at `0`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumberedRenderer;
+
+impl ChainRenderer for NumberedRenderer {
+    fn render(&self, err: &dyn AnyChained) -> String {
+        let mut out = String::new();
+        for (i, e) in err.any_iter().enumerate() {
+            let _ = writeln!(out, "{}: {}", i, e);
+        }
+        out
+    }
+}
@@ -0,0 +1,229 @@
+//! Tests which need `RUST_BACKTRACE` to be set. They live in their own
+//! binary so they don't race with tests changing the environment.
+#![cfg(feature = "backtrace")]
+
+#[macro_use]
+extern crate error_chain;
+
+use std::env;
+use std::thread;
+
+error_chain! {
+    errors {
+        Test
+    }
+}
+
+fn enable_backtrace() {
+    env::set_var("RUST_BACKTRACE", "1");
+}
+
+#[test]
+fn records_thread() {
+    enable_backtrace();
+
+    let err = thread::Builder::new()
+        .name("worker".into())
+        .spawn(|| Error::from(ErrorKind::Test))
+        .unwrap()
+        .join()
+        .unwrap();
+
+    let (name, _) = err.thread().unwrap();
+    assert_eq!(name, Some("worker".to_string()));
+    assert!(format!("{}", error_chain::ChainedError::display_detailed(&err))
+        .contains("thread: worker\n"));
+    assert!(!format!("{}", error_chain::ChainedError::display(&err)).contains("thread: worker"));
+}
+
+#[test]
+fn report_has_backtrace() {
+    enable_backtrace();
+
+    let res: Result<()> = Err(ErrorKind::Test.into());
+    assert!(res.into_report().unwrap_err().backtrace.is_some());
+}
+
+#[test]
+fn display_chain_stable() {
+    use error_chain::ChainedError;
+
+    enable_backtrace();
+
+    fn render() -> String {
+        let err = Error::from(ErrorKind::Test).chain_err(|| "top");
+        err.display_chain_stable().to_string()
+    }
+
+    let output = render();
+    assert_eq!(output, "Error: top\nCaused by: Test\n<backtrace omitted>\n");
+    assert_eq!(output, render());
+}
+
+#[test]
+fn trace_err() {
+    enable_backtrace();
+
+    let mut err = Error::from(ErrorKind::Test);
+    err.1.backtrace = None;
+    let res: Result<()> = Err(err);
+    assert!(res.trace_err().unwrap_err().backtrace().is_some());
+
+    let res: ::std::result::Result<(), ::std::fmt::Error> = Err(::std::fmt::Error);
+    let err = res.trace_err().unwrap_err();
+    assert!(err.backtrace().is_some());
+    assert_eq!(err.iter().count(), 2);
+}
+
+#[test]
+fn display_chain_no_backtrace() {
+    use error_chain::ChainedError;
+
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test).chain_err(|| "top");
+    assert!(err.backtrace().is_some());
+    assert_eq!(err.display_chain_no_backtrace().to_string(), "Error: top\nCaused by: Test\n");
+}
+
+#[test]
+fn resolved_frames() {
+    use error_chain::{resolved_frames, Backtrace};
+
+    fn capture() -> Backtrace {
+        Backtrace::new()
+    }
+
+    let backtraces: Vec<Backtrace> = (0..2).map(|_| capture()).collect();
+    let frames = resolved_frames(&backtraces[0]);
+    assert!(frames.iter().any(|f| f.name.as_ref().is_some_and(|n| n.contains("capture"))));
+    assert_eq!(frames, resolved_frames(&backtraces[1]));
+}
+
+#[test]
+fn backtrace_user_only() {
+    use error_chain::ChainedError;
+
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test);
+    let frames = err.backtrace_user_only("tests/").unwrap();
+    assert!(!frames.is_empty());
+    assert!(frames.iter().all(|f| f.filename.as_ref().unwrap().ends_with("tests/backtrace.rs")));
+    assert!(frames.len() < error_chain::resolved_frames(err.backtrace().unwrap()).len());
+    assert!(err.backtrace_user_only("no/such/dir/").unwrap().is_empty());
+}
+
+#[test]
+fn backtrace_bytes() {
+    use error_chain::{resolve_backtrace_bytes, resolved_frames, ChainedError};
+
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test);
+    let bytes = err.backtrace_bytes().unwrap();
+    assert!(bytes.len() < err.backtrace().unwrap().frames().len() * 8);
+    assert_eq!(resolve_backtrace_bytes(&bytes), resolved_frames(err.backtrace().unwrap()));
+}
+
+#[test]
+fn reset_cause_and_backtrace() {
+    enable_backtrace();
+
+    let mut err = Error::from(ErrorKind::Test).chain_err(|| "top");
+    assert_eq!(err.iter().count(), 2);
+    assert!(err.backtrace().is_some());
+
+    err.reset_cause_and_backtrace();
+    assert_eq!(err.iter().count(), 1);
+    assert!(err.backtrace().is_none());
+    assert_eq!(err.to_string(), "top");
+}
+
+#[test]
+fn display_chain_indented() {
+    use error_chain::ChainedError;
+
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test).chain_err(|| "mid").chain_err(|| "top");
+    let output = err.display_chain_indented().to_string();
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("Error: top"));
+    assert_eq!(lines.next(), Some("  caused by: mid"));
+    assert_eq!(lines.next(), Some("    caused by: Test"));
+    assert!(lines.all(|line| line.starts_with("    ")));
+}
+
+#[test]
+fn from_parts() {
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test).chain_err(|| "top");
+    let err = Error::from_parts(err.into_parts());
+    assert_eq!(err.to_string(), "top");
+    assert_eq!(err.iter().nth(1).unwrap().to_string(), "Test");
+    assert!(err.backtrace().is_some());
+
+    let mut parts = Error::from(ErrorKind::Test).into_parts();
+    parts.backtrace = None;
+    let err = Error::from_parts(parts);
+    match *err.kind() {
+        ErrorKind::Test => {}
+        _ => panic!("expected the test kind"),
+    }
+    assert!(err.backtrace().is_none());
+}
+
+#[test]
+fn no_backtrace_foreign_link() {
+    enable_backtrace();
+
+    mod parse {
+        error_chain! {
+            foreign_links {
+                Int(::std::num::ParseIntError) no_backtrace;
+                Float(::std::num::ParseFloatError);
+            }
+        }
+    }
+
+    let err = parse::Error::from("x".parse::<i32>().unwrap_err());
+    assert!(err.backtrace().is_none());
+    let err = parse::Error::from("x".parse::<f32>().unwrap_err());
+    assert!(err.backtrace().is_some());
+}
+
+#[test]
+fn iter_with_backtrace() {
+    use error_chain::ChainedError;
+
+    enable_backtrace();
+
+    let err = Error::from(ErrorKind::Test).chain_err(|| "top");
+    let levels: Vec<_> = err.iter_with_backtrace().collect();
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[0].0.to_string(), "top");
+    assert!(levels[0].1.is_some());
+    assert_eq!(levels[1].0.to_string(), "Test");
+    assert!(levels[1].1.is_some());
+
+    let foreign = ::std::io::Error::new(::std::io::ErrorKind::NotFound, "root");
+    let err = Error::with_chain(foreign, "top");
+    let backtraces: Vec<bool> = err.iter_with_backtrace().map(|(_, b)| b.is_some()).collect();
+    assert_eq!(backtraces, vec![true, false]);
+}
+
+#[test]
+fn with_backtrace_foreign() {
+    use error_chain::Backtrace;
+
+    enable_backtrace();
+
+    let backtrace = Backtrace::new();
+    let frames = backtrace.frames().len();
+    let res: ::std::result::Result<(), ::std::io::Error> =
+        Err(::std::io::Error::new(::std::io::ErrorKind::NotFound, "file not found"));
+    let err = res.with_backtrace(backtrace).unwrap_err();
+    assert_eq!(err.backtrace().unwrap().frames().len(), frames);
+}
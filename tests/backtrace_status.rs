@@ -0,0 +1,35 @@
+//! `backtrace_status` depends on `RUST_BACKTRACE` and on the global
+//! `set_backtraces_enabled`, so it is tested in its own binary.
+
+#[macro_use]
+extern crate error_chain;
+
+use error_chain::BacktraceStatus;
+
+error_chain! {}
+
+#[test]
+#[cfg(feature = "backtrace")]
+fn backtrace_status() {
+    ::std::env::set_var("RUST_BACKTRACE", "1");
+    assert_eq!(error_chain::backtrace_status(), BacktraceStatus::EnvVarOn);
+    assert!(Error::from("on").backtrace().is_some());
+
+    error_chain::set_backtraces_enabled(false);
+    assert_eq!(error_chain::backtrace_status(), BacktraceStatus::ProgrammaticallyDisabled);
+    assert!(Error::from("disabled").backtrace().is_none());
+    error_chain::set_backtraces_enabled(true);
+
+    ::std::env::set_var("RUST_BACKTRACE", "0");
+    assert_eq!(error_chain::backtrace_status(), BacktraceStatus::EnvVarOff);
+    ::std::env::remove_var("RUST_BACKTRACE");
+    assert_eq!(error_chain::backtrace_status(), BacktraceStatus::EnvVarOff);
+    assert!(Error::from("off").backtrace().is_none());
+}
+
+#[test]
+#[cfg(not(feature = "backtrace"))]
+fn backtrace_status() {
+    error_chain::set_backtraces_enabled(false);
+    assert_eq!(error_chain::backtrace_status(), BacktraceStatus::FeatureDisabled);
+}
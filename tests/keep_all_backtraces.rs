@@ -0,0 +1,52 @@
+//! `keep_all_backtraces` is global, so it is tested in its own binary.
+#![cfg(feature = "backtrace")]
+
+#[macro_use]
+extern crate error_chain;
+
+use error_chain::ChainedError;
+use std::sync::Mutex;
+
+error_chain! {}
+
+/// Serializes the tests changing the global setting.
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn keep_all_backtraces() {
+    let _lock = LOCK.lock().unwrap();
+    ::std::env::set_var("RUST_BACKTRACE", "1");
+
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.backtraces().len(), 1);
+
+    error_chain::keep_all_backtraces(true);
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.backtraces().len(), 2);
+    let err = Error::from("root").chain_err(|| "middle").chain_err(|| "top");
+    assert_eq!(err.backtraces().len(), 3);
+    let output = err.display().to_string();
+    assert!(output.contains("Backtrace 1:\n"));
+    assert!(output.contains("Backtrace 3:\n"));
+
+    error_chain::keep_all_backtraces(false);
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.backtraces().len(), 1);
+}
+
+#[test]
+fn all_resolved_frames() {
+    let _lock = LOCK.lock().unwrap();
+    ::std::env::set_var("RUST_BACKTRACE", "1");
+    error_chain::keep_all_backtraces(true);
+
+    let err = Error::from("root").chain_err(|| "top");
+    let indices: Vec<usize> = err.all_resolved_frames().map(|(i, _)| i).collect();
+    assert!(indices.contains(&0));
+    assert!(indices.contains(&1));
+    assert!(indices.windows(2).all(|w| w[0] <= w[1]));
+    let frames: Vec<_> = err.all_resolved_frames().map(|(_, f)| f).collect();
+    assert!(frames.iter().any(|f| f.name.as_ref().is_some_and(|n| n.contains("all_resolved_frames"))));
+
+    error_chain::keep_all_backtraces(false);
+}
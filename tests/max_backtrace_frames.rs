@@ -0,0 +1,26 @@
+//! `set_max_backtrace_frames` is global, so it is tested in its own binary.
+#![cfg(feature = "backtrace")]
+
+#[macro_use]
+extern crate error_chain;
+
+error_chain! {}
+
+#[test]
+fn max_backtrace_frames() {
+    ::std::env::set_var("RUST_BACKTRACE", "1");
+
+    error_chain::set_max_backtrace_frames(Some(5));
+    let err = Error::from("limited");
+    let frames = err.backtrace().unwrap().frames();
+    assert!(frames.len() <= 5);
+    // The frames capturing the backtrace are skipped, not those of the
+    // caller.
+    assert!(frames.iter().flat_map(|f| f.symbols()).any(|s| {
+        s.name().is_some_and(|n| n.to_string().contains("max_backtrace_frames::max_backtrace_frames"))
+    }));
+
+    error_chain::set_max_backtrace_frames(None);
+    let err = Error::from("unlimited");
+    assert!(err.backtrace().unwrap().frames().len() > 5);
+}
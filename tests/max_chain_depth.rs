@@ -0,0 +1,33 @@
+//! `set_max_chain_depth` is global, so it is tested in its own binary.
+
+#[macro_use]
+extern crate error_chain;
+
+error_chain! {}
+
+#[test]
+fn max_chain_depth() {
+    error_chain::set_max_chain_depth(Some(3));
+
+    let mut err = Error::from("0");
+    for i in 1..6 {
+        err = err.chain_err(|| i.to_string());
+        assert!(err.chain_len() <= 3);
+    }
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["5", "4: 3: 2: 1: 0"]);
+
+    let res: Result<()> = Err(err).chain_err(|| "top");
+    assert_eq!(res.unwrap_err().chain_len(), 3);
+
+    let mut errors = error_chain::ErrorAccumulator::new();
+    errors.extend((0..5).map(|i| Error::from(i.to_string())));
+    let messages: Vec<String> = errors.into_error().unwrap().iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["0", "1: 2: 3: 4"]);
+
+    error_chain::set_max_chain_depth(Some(1));
+    assert_eq!(Error::from("root").chain_err(|| "top").chain_len(), 1);
+
+    error_chain::set_max_chain_depth(None);
+    assert_eq!(Error::from("root").chain_err(|| "top").chain_len(), 2);
+}
@@ -26,3 +26,35 @@ mod closure {
     use super::*;
     quick_main!(|| -> Result<()> { Ok(()) });
 }
+
+mod exit_code {
+    use super::*;
+    quick_main!(run);
+
+    fn run() -> Result<error_chain::ExitCode> {
+        Ok(error_chain::ExitCode(3))
+    }
+}
+
+#[test]
+fn exit_code() {
+    use error_chain::{exit_code, ExitCode};
+
+    assert_eq!(exit_code::<_, Error>(Ok(ExitCode(3))), 3);
+    assert_eq!(exit_code::<_, Error>(Ok(ExitCode::from(7))), 7);
+    assert_eq!(exit_code::<_, Error>(Ok(())), 0);
+    assert_eq!(exit_code::<(), _>(Err(Error::from("boom"))), 1);
+    assert_eq!(ExitCode(3).to_string(), "3");
+}
+
+#[test]
+#[cfg(unix)]
+fn exit_code_clamped() {
+    use error_chain::{exit_code, ExitCode};
+
+    assert_eq!(exit_code::<_, Error>(Ok(ExitCode(255))), 255);
+    assert_eq!(exit_code::<_, Error>(Ok(ExitCode(300))), 44);
+    assert_eq!(exit_code::<_, Error>(Ok(-1)), 255);
+    assert_eq!(exit_code::<_, Error>(Ok(ExitCode(256))), 1);
+    assert_eq!(exit_code::<_, Error>(Ok(0)), 0);
+}
@@ -1,4 +1,6 @@
 #![allow(dead_code)]
+#![allow(unknown_lints, bare_trait_objects, deprecated, invalid_doc_attributes)]
+#![allow(clippy::invisible_characters)]
 
 #[macro_use]
 extern crate error_chain;
@@ -197,6 +199,18 @@ fn empty() {
     error_chain!{};
 }
 
+#[test]
+fn derive() {
+    error_chain! {
+        derive {
+            Debug, Clone;
+        }
+    }
+
+    let kind = ErrorKind::Msg("cloned".to_owned());
+    assert_eq!(kind.clone().to_string(), "cloned");
+}
+
 #[test]
 #[cfg(feature = "backtrace")]
 fn has_backtrace_depending_on_env() {
@@ -404,6 +418,41 @@ mod attributes_test {
     }
 }
 
+/// A link gated by a feature, tested with the feature enabled and disabled.
+mod cfg_links {
+    #[cfg(feature = "backtrace")]
+    pub mod optional {
+        error_chain! {
+            errors { Missing }
+        }
+    }
+
+    error_chain! {
+        links {
+            Optional(optional::Error, optional::ErrorKind) #[cfg(feature = "backtrace")];
+        }
+
+        errors { Local }
+    }
+
+    #[test]
+    fn cfg_links() {
+        #[cfg(feature = "backtrace")]
+        {
+            let err: Error = optional::Error::from(optional::ErrorKind::Missing).into();
+            match *err.kind() {
+                ErrorKind::Optional(optional::ErrorKind::Missing) => {}
+                ref kind => panic!("Unexpected error kind: {:?}", kind),
+            }
+            assert_eq!(err.kind_path(), "Optional.Missing");
+        }
+
+        let err = Error::from(ErrorKind::Local);
+        assert_eq!(err.kind_path(), "Local");
+        assert!(!err.has_foreign_root());
+    }
+}
+
 #[test]
 fn with_result() {
     error_chain! {
@@ -444,6 +493,21 @@ fn documentation() {
     }
 }
 
+/// Fails to compile without the `allow` option.
+#[cfg(test)]
+#[deny(missing_docs)]
+pub mod allow_option {
+    error_chain! {
+        errors {
+            Undocumented
+        }
+
+        options {
+            allow(missing_docs);
+        }
+    }
+}
+
 #[cfg(test)]
 mod multiple_error_same_mod {
     error_chain! {
@@ -545,6 +609,38 @@ fn ensure() {
     assert!(foo(0).is_err());
 }
 
+#[test]
+fn ensure_with_cause() {
+    use std::io;
+
+    error_chain! {
+        errors { Bar }
+    }
+
+    fn foo(x: u8) -> Result<()> {
+        let cause = io::Error::new(io::ErrorKind::NotFound, "no such value");
+        ensure!(x == 42, ErrorKind::Bar, cause);
+        Ok(())
+    }
+
+    fn bar(x: u8) -> Result<()> {
+        ensure!(x == 42, "bad value: {}", x);
+        Ok(())
+    }
+
+    assert!(foo(42).is_ok());
+    let err = foo(0).unwrap_err();
+    match *err.kind() {
+        ErrorKind::Bar => {}
+        _ => panic!("unexpected kind: {:?}", err),
+    }
+    let cause = ::std::error::Error::source(&err).unwrap();
+    assert_eq!(cause.to_string(), "no such value");
+    assert!(cause.is::<io::Error>());
+
+    assert_eq!(bar(0).unwrap_err().to_string(), "bad value: 0");
+}
+
 /// Since the `types` declaration is a list of symbols, check if we
 /// don't change their meaning or order.
 #[test]
@@ -630,3 +726,1815 @@ fn trailing_comma_in_errors_impl() {
         }
     };
 }
+
+/// `Error` must stay `Send`; this fails to compile if it isn't.
+#[test]
+fn error_is_send() {
+    fn assert_send<T: Send>() {}
+
+    assert_send::<error_chain::mock::Error>();
+    assert_send::<error_chain::mock::ErrorKind>();
+    assert_send::<error_chain::State>();
+
+    // Checks the type the cause is actually stored as, whatever it is.
+    fn assert_send_value<T: Send>(_: &T) {}
+    let state = error_chain::State::default();
+    assert_send_value(&state.next_error);
+}
+
+#[test]
+fn kind_message() {
+    error_chain! {
+        errors { Foo }
+    }
+
+    assert_eq!(Error::from("msg").kind().message(), Some("msg"));
+    assert_eq!(ErrorKind::Foo.message(), None);
+}
+
+#[test]
+#[cfg(feature = "context-trace")]
+fn context_trace() {
+    error_chain! {}
+
+    fn root() -> Result<()> {
+        Err("root".into())
+    }
+
+    let err = root().chain_err(|| "first").unwrap_err().chain_err(|| "second");
+    let trace = err.context_trace();
+    assert_eq!(trace.len(), 2);
+    assert!(trace.iter().all(|&(file, _)| file.ends_with("tests.rs")));
+
+    fn bails() -> Result<()> {
+        bail_traced!("bailed")
+    }
+    assert_eq!(bails().unwrap_err().context_trace().len(), 1);
+
+    fn plain_bail() -> Result<()> {
+        bail!("bailed")
+    }
+    assert!(plain_bail().unwrap_err().context_trace().is_empty());
+}
+
+#[test]
+fn map_cause() {
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.iter().count(), 2);
+
+    let err = err.map_cause(|_| None);
+    assert_eq!(err.iter().count(), 1);
+    assert_eq!(err.to_string(), "top");
+}
+
+#[test]
+fn has_foreign_root() {
+    use std::io;
+
+    error_chain! {
+        foreign_links {
+            Io(io::Error);
+        }
+    }
+
+    fn io_error() -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, "io")
+    }
+
+    let err = Error::with_chain(io_error(), "top").chain_err(|| "more");
+    assert!(err.has_foreign_root());
+    assert!(Error::from(io_error()).has_foreign_root());
+
+    let err = Error::from("root").chain_err(|| "top");
+    assert!(!err.has_foreign_root());
+}
+
+#[test]
+#[cfg(error_chain_nightly)]
+fn to_report() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top");
+    let report = err.to_report().to_string();
+    assert!(report.starts_with("top"));
+    assert!(report.contains("Caused by:"));
+    assert!(report.contains("root"));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn to_json_tree() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let tree = Error::from("root").chain_err(|| "top").to_json_tree();
+    assert_eq!(tree["message"], "top");
+    assert_eq!(tree["cause"]["message"], "root");
+    assert!(tree["cause"]["cause"].is_null());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn with_field() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let tree = Error::from("root")
+        .with_field("path", "/tmp/foo")
+        .chain_err(|| "top")
+        .with_field("attempt", 3)
+        .to_json_tree();
+    assert_eq!(tree["fields"]["attempt"], 3);
+    assert_eq!(tree["cause"]["fields"]["path"], "/tmp/foo");
+    assert!(tree["cause"]["cause"].get("fields").is_none());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn deserialize_kind() {
+    use error_chain::serde_json;
+
+    error_chain! {
+        errors {
+            NotFound {
+                description("not found")
+            }
+            Timeout
+        }
+
+        options {
+            deserialize_kind;
+        }
+    }
+
+    let kind: ErrorKind = serde_json::from_str("\"Timeout\"").unwrap();
+    match kind {
+        ErrorKind::Timeout => {}
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    let kind: ErrorKind = serde_json::from_str("{\"Msg\": \"text\"}").unwrap();
+    assert_eq!(kind.message(), Some("text"));
+    assert!(serde_json::from_str::<ErrorKind>("\"Unknown\"").is_err());
+    assert!(serde_json::from_str::<ErrorKind>("{\"Timeout\": null}").is_err());
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn display_chain_colored() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top");
+    let colored = err.display_chain_colored().to_string();
+    assert!(colored.starts_with("\x1b[31mError: top\x1b[0m\n\x1b[2mCaused by: root\x1b[0m\n"),
+            "{:?}", colored);
+    assert_eq!(err.display_chain_colored_if_tty(true).to_string(), colored);
+
+    let plain = err.display_chain_colored_if_tty(false).to_string();
+    assert!(!plain.contains('\x1b'));
+    assert_eq!(plain, err.display().to_string());
+}
+
+#[test]
+#[cfg(feature = "timestamp")]
+fn created_at() {
+    use std::time::{Duration, SystemTime};
+
+    error_chain! {}
+
+    let before = SystemTime::now();
+    let err = Error::from("root").chain_err(|| "top");
+    let after = SystemTime::now();
+
+    let created_at = err.created_at().unwrap();
+    assert!(created_at >= before && created_at <= after);
+    assert!(SystemTime::now().duration_since(created_at).unwrap() < Duration::from_secs(60));
+}
+
+#[test]
+#[cfg(not(feature = "timestamp"))]
+fn created_at() {
+    error_chain! {}
+
+    assert!(Error::from("root").created_at().is_none());
+}
+
+#[test]
+#[cfg(feature = "anyhow")]
+fn into_anyhow() {
+    use error_chain::{anyhow, ChainedError};
+
+    error_chain! {}
+
+    fn fails() -> anyhow::Result<()> {
+        let res: Result<()> = Err(Error::from("root").chain_err(|| "middle").chain_err(|| "top"));
+        res.map_err(ChainedError::into_anyhow)?;
+        Ok(())
+    }
+
+    let err = fails().unwrap_err();
+    let messages: Vec<String> = err.chain().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "middle", "root"]);
+}
+
+#[test]
+fn bail_chain() {
+    error_chain! {
+        errors {
+            Parse
+        }
+    }
+
+    fn parse() -> Result<()> {
+        Err(ErrorKind::Parse.into())
+    }
+
+    fn load(name: &str) -> Result<()> {
+        if let Err(e) = parse() {
+            bail_chain!(e, "failed to load {}", name);
+        }
+        Ok(())
+    }
+
+    fn load_kind() -> Result<()> {
+        match parse() {
+            Err(e) => bail_chain!(e, ErrorKind::Msg("failed".into())),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    let err = load("config").unwrap_err();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["failed to load config", "Parse"]);
+    assert_eq!(load_kind().unwrap_err().iter().count(), 2);
+}
+
+#[test]
+#[cfg(feature = "failure")]
+fn into_failure() {
+    use error_chain::{failure, ChainedError};
+
+    error_chain! {}
+
+    fn fails() -> ::std::result::Result<(), failure::Error> {
+        let res: Result<()> = Err(Error::from("root").chain_err(|| "middle").chain_err(|| "top"));
+        res.map_err(ChainedError::into_failure)?;
+        Ok(())
+    }
+
+    let err = fails().unwrap_err();
+    let messages: Vec<String> = err.iter_chain().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "middle", "root"]);
+}
+
+#[test]
+fn bail_fn() {
+    error_chain! {}
+
+    fn fails() -> Result<()> {
+        bail_fn!("{} failed", "it")
+    }
+
+    assert_eq!(fails().unwrap_err().to_string(), "tests::bail_fn::fails: it failed");
+}
+
+#[test]
+fn into_report() {
+    error_chain! {}
+
+    let res: Result<()> = Err(Error::from("root").chain_err(|| "top"));
+    let report = res.into_report().unwrap_err();
+    assert_eq!(report.summary, "top");
+    assert_eq!(report.causes, vec!["root".to_string()]);
+}
+
+#[test]
+fn kind_path() {
+    use error_chain::ChainedError;
+
+    mod utils {
+        pub mod inner {
+            error_chain! {
+                errors {
+                    BadStuff
+                }
+            }
+        }
+
+        error_chain! {
+            links {
+                Inner(inner::Error, inner::ErrorKind);
+            }
+        }
+    }
+
+    error_chain! {
+        links {
+            Utils(utils::Error, utils::ErrorKind);
+        }
+
+        foreign_links {
+            Io(::std::io::Error);
+        }
+    }
+
+    let inner = utils::inner::Error::from(utils::inner::ErrorKind::BadStuff);
+    let err: Error = utils::Error::from(inner).into();
+    assert_eq!(ChainedError::kind_path(&err), "Utils.Inner.BadStuff");
+    assert_eq!(err.kind().variant_name(), "Utils");
+
+    let err: Error = ::std::io::Error::new(::std::io::ErrorKind::NotFound, "missing").into();
+    assert_eq!(err.kind_path(), "Io");
+    assert_eq!(Error::from("msg").kind_path(), "Msg");
+}
+
+#[test]
+fn foreign_link_context() {
+    use std::io;
+
+    error_chain! {
+        foreign_links {
+            Io(io::Error) context("while doing IO");
+            Fmt(::std::fmt::Error);
+        }
+    }
+
+    fn read() -> Result<()> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "file not found"))?;
+        Ok(())
+    }
+
+    let err = read().unwrap_err();
+    assert_eq!(err.to_string(), "while doing IO");
+    let cause = ::std::error::Error::source(&err).unwrap();
+    assert_eq!(cause.to_string(), "file not found");
+    match cause.downcast_ref::<Error>().unwrap().kind() {
+        &ErrorKind::Io(_) => {}
+        _ => panic!("expected the io error"),
+    }
+
+    let err = Error::from(::std::fmt::Error);
+    match *err.kind() {
+        ErrorKind::Fmt(_) => {},
+        _ => panic!("expected the fmt error"),
+    }
+}
+
+#[test]
+fn retain_family() {
+    use std::io;
+
+    error_chain! {
+        foreign_links {
+            Io(io::Error);
+        }
+    }
+
+    let foreign = io::Error::new(io::ErrorKind::NotFound, "file not found");
+    let err = Error::with_chain(foreign, "mid").chain_err(|| "top");
+    assert_eq!(err.chain_len(), 3);
+
+    let err = err.retain_family();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "mid"]);
+
+    let linked = Error::from(io::Error::new(io::ErrorKind::NotFound, "file not found"))
+        .chain_err(|| "top")
+        .retain_family();
+    assert_eq!(linked.chain_len(), 2);
+}
+
+#[test]
+fn expect_chain() {
+    use std::{io, panic};
+
+    error_chain! {}
+
+    fn payload<F: FnOnce() + panic::UnwindSafe>(f: F) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    let message = payload(|| {
+        let res: Result<()> = Err(Error::from("inner").chain_err(|| "top"));
+        res.expect_chain("loading failed");
+    });
+    assert!(message.starts_with("loading failed: Error: top\n"), "{}", message);
+    assert!(message.contains("Caused by: inner\n"), "{}", message);
+
+    let message = payload(|| {
+        let res: ::std::result::Result<(), io::Error> =
+            Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+        res.expect_chain("reading failed");
+    });
+    assert_eq!(message, "reading failed: Error: not found\n");
+
+    let ok: Result<u8> = Ok(3);
+    assert_eq!(ok.expect_chain("unused"), 3);
+}
+
+#[test]
+fn prepend_root() {
+    use std::io;
+
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top").prepend_root("triggered by config X");
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "root", "triggered by config X"]);
+
+    let foreign = io::Error::new(io::ErrorKind::NotFound, "not found");
+    let err = Error::with_chain(foreign, "top").prepend_root("annotation");
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "annotation", "not found"]);
+}
+
+#[test]
+fn transcribe() {
+    use std::io;
+    use error_chain::ChainedError;
+
+    mod other {
+        error_chain! {
+            errors { Other }
+        }
+    }
+
+    error_chain! {
+        errors { Custom }
+    }
+
+    let foreign = io::Error::new(io::ErrorKind::NotFound, "not found");
+    let err = Error::with_chain(foreign, ErrorKind::Custom).chain_err(|| "top");
+    let copy: other::Error = err.transcribe();
+    let messages: Vec<String> = copy.iter().map(|e| e.to_string()).collect();
+    let original: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, original);
+    assert!(::std::error::Error::source(&copy).unwrap().is::<other::Error>());
+    match *copy.kind() {
+        other::ErrorKind::Msg(ref s) => assert_eq!(s, "top"),
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn as_ref_kind() {
+    error_chain! {
+        errors { Custom }
+    }
+
+    fn is_custom<K: AsRef<ErrorKind>>(kind: K) -> bool {
+        matches!(*kind.as_ref(), ErrorKind::Custom)
+    }
+
+    let err = Error::from(ErrorKind::Custom);
+    assert!(is_custom(&err));
+    assert!(!is_custom(Error::from("msg")));
+}
+
+#[test]
+fn walk() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("inner").chain_err(|| "top");
+    let mut levels = Vec::new();
+    err.walk(|depth, e| levels.push((depth, e.to_string())));
+    assert_eq!(levels, vec![(0, "top".to_owned()), (1, "inner".to_owned())]);
+}
+
+#[test]
+fn error_at() {
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.error_at(0).unwrap().to_string(), "top");
+    assert_eq!(err.error_at(1).unwrap().to_string(), "root");
+    assert!(err.error_at(2).is_none());
+}
+
+#[test]
+fn same_kind_as() {
+    error_chain! {
+        errors {
+            Custom
+        }
+    }
+
+    let a = Error::from("a");
+    let b = Error::from("b").chain_err(|| "c");
+    assert!(Error::from("a").same_kind_as(&Error::from("b")));
+    assert!(a.same_kind_as(&b));
+    assert!(!a.same_kind_as(&Error::from(ErrorKind::Custom)));
+}
+
+#[test]
+fn with_context_kind() {
+    use std::io;
+
+    error_chain! {
+        errors {
+            Missing(path: String) {
+                display("missing file: {}", path)
+            }
+        }
+    }
+
+    let res: ::std::result::Result<(), io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+    let err = res.with_context_kind(|e| match e.kind() {
+        io::ErrorKind::NotFound => ErrorKind::Missing("config.toml".into()),
+        _ => ErrorKind::Msg(e.to_string()),
+    }).unwrap_err();
+
+    match *err.kind() {
+        ErrorKind::Missing(ref path) => assert_eq!(path, "config.toml"),
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    let cause = ::std::error::Error::source(&err).unwrap().downcast_ref::<io::Error>().unwrap();
+    assert_eq!(cause.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn map_chain() {
+    use std::num::ParseIntError;
+
+    error_chain! {}
+
+    let ok: ::std::result::Result<u8, ParseIntError> = "3".parse();
+    assert_eq!(ok.map_chain(|n| n * 2, || "parsing failed").unwrap(), 6);
+
+    let err: ::std::result::Result<u8, ParseIntError> = "x".parse();
+    let err = err.map_chain(|n| n * 2, || "parsing failed").unwrap_err();
+    assert_eq!(err.to_string(), "parsing failed");
+    assert!(::std::error::Error::source(&err).unwrap().is::<ParseIntError>());
+}
+
+#[test]
+fn chain_err_kv() {
+    use std::io;
+
+    error_chain! {}
+
+    let res: ::std::result::Result<(), io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+    let err = res.chain_err_kv("path", "config.toml").unwrap_err();
+    assert_eq!(err.to_string(), "path=config.toml");
+    assert!(::std::error::Error::source(&err).unwrap().is::<io::Error>());
+
+    let res: Result<()> = Err(err);
+    let err = res.chain_err_kv("attempt", 3).chain_err(|| "loading failed").unwrap_err();
+    assert_eq!(err.context_kvs(),
+               vec![("attempt", "3".to_owned()), ("path", "config.toml".to_owned())]);
+    assert!(Error::from("alone").context_kvs().is_empty());
+}
+
+#[test]
+fn chain_err_dyn() {
+    use std::io;
+
+    error_chain! {
+        errors {
+            Missing(path: String) {
+                display("missing file: {}", path)
+            }
+        }
+    }
+
+    let path = String::from("config.toml");
+    let make: Box<FnOnce() -> ErrorKind> = Box::new(move || ErrorKind::Missing(path));
+    let res: ::std::result::Result<(), io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+    let err = res.chain_err_dyn(make).unwrap_err();
+
+    match *err.kind() {
+        ErrorKind::Missing(ref path) => assert_eq!(path, "config.toml"),
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    assert!(::std::error::Error::source(&err).unwrap().is::<io::Error>());
+
+    let ok: ::std::result::Result<u8, io::Error> = Ok(1);
+    assert_eq!(ok.chain_err_dyn(Box::new(|| ErrorKind::Missing("unused".into()))).unwrap(), 1);
+}
+
+#[test]
+fn stringify_err() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "top");
+    assert_eq!(err.display_chain_inline().to_string(), "top: root");
+
+    let res: Result<()> = Err(err);
+    assert_eq!(res.stringify_err(), Err("top: root".to_owned()));
+
+    let res: ::std::result::Result<(), ::std::fmt::Error> = Err(::std::fmt::Error);
+    assert_eq!(res.stringify_err(), Err("an error occurred when formatting an argument".to_owned()));
+}
+
+#[test]
+fn cause_stats() {
+    use std::io;
+    use error_chain::{CauseStats, ChainedError};
+
+    error_chain! {}
+
+    let foreign = io::Error::new(io::ErrorKind::NotFound, "root");
+    let err = Error::with_chain(foreign, "first").chain_err(|| "second").chain_err(|| "top");
+    assert_eq!(err.cause_stats(), CauseStats { family: 2, foreign: 1 });
+    assert_eq!(Error::from("alone").cause_stats(), CauseStats::default());
+}
+
+#[test]
+fn display_chain_with_prefix() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("inner").chain_err(|| "top");
+    let output = err.display_chain_with_prefix(false).to_string();
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("top"));
+    assert_eq!(lines.next(), Some("Caused by: inner"));
+    assert!(err.display_chain_with_prefix(true).to_string().starts_with("Error: top\n"));
+}
+
+#[test]
+fn format_report() {
+    use std::io;
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let root = io::Error::new(io::ErrorKind::NotFound, "root");
+    let mut err = Error::with_chain(root, "inner").chain_err(|| "top");
+    err.ensure_backtrace();
+    let report = err.format_report();
+    for header in &["== Summary ==", "== Causes ==", "== Thread ==", "== Backtrace =="] {
+        assert!(report.contains(header), "missing {} in {}", header, report);
+    }
+    assert!(report.starts_with("== Summary ==\ntop\n"));
+    assert!(report.contains("1. inner\n2. root\n"));
+    if cfg!(feature = "backtrace") {
+        assert!(!report.contains("== Backtrace ==\nnot captured"));
+        assert!(!report.contains("== Thread ==\nunknown"));
+    }
+
+    let report = Error::from("alone").format_report();
+    assert!(report.contains("== Causes ==\nnone\n"));
+}
+
+#[test]
+fn kind_owned() {
+    use error_chain::ChainedError;
+
+    error_chain! {
+        derive {
+            Debug, Clone;
+        }
+
+        errors {
+            Retry(attempts: u32) {
+                description("retry")
+                display("retry after {} attempts", attempts)
+            }
+        }
+    }
+
+    let err = Error::from(ErrorKind::Retry(3)).chain_err(|| ErrorKind::Retry(4));
+    let kind = err.kind_owned();
+    drop(err);
+    match kind {
+        ErrorKind::Retry(4) => {}
+        _ => panic!("Unexpected kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn multi() {
+    error_chain! {
+        errors {
+            Invalid(field: &'static str) {
+                description("invalid field")
+                display("invalid field '{}'", field)
+            }
+        }
+
+        options {
+            multi;
+        }
+    }
+
+    let errors = vec![Error::from(ErrorKind::Invalid("name")),
+                      Error::from(ErrorKind::Invalid("age")),
+                      Error::from("missing id")];
+    let err = Error::from(errors);
+    match *err.kind() {
+        ErrorKind::Multiple(ref errors) => assert_eq!(errors.len(), 3),
+        _ => panic!("Unexpected kind: {:?}", err),
+    }
+    assert_eq!(err.to_string(),
+               "3 errors: [1] invalid field 'name'; [2] invalid field 'age'; [3] missing id");
+    assert_eq!(Error::from(Vec::new()).to_string(), "no errors");
+}
+
+#[test]
+fn display_with_cause() {
+    use std::io;
+
+    error_chain! {
+        errors {
+            LoadFailed(path: String) {
+                description("load failed")
+                display_with_cause(|f, path, cause| match cause {
+                    Some(cause) => write!(f, "failed to load {}: {}", path, cause),
+                    None => write!(f, "failed to load {}", path),
+                })
+            }
+            Copy(from: String, to: String) {
+                description("copy failed")
+                display_with_cause(|f, (from, to), _| write!(f, "failed to copy {} to {}", from, to))
+            }
+        }
+    }
+
+    let cause = io::Error::new(io::ErrorKind::NotFound, "no such file");
+    let err = Error::with_chain(cause, ErrorKind::LoadFailed("a.toml".into()));
+    assert_eq!(err.to_string(), "failed to load a.toml: no such file");
+    assert_eq!(Error::from(ErrorKind::LoadFailed("b.toml".into())).to_string(),
+               "failed to load b.toml");
+    assert_eq!(ErrorKind::LoadFailed("a.toml".into()).to_string(), "load failed");
+
+    let err = Error::from(ErrorKind::Copy("a".into(), "b".into()));
+    assert_eq!(err.to_string(), "failed to copy a to b");
+}
+
+#[test]
+fn shared_cause() {
+    use std::io;
+    use error_chain::SharedCause;
+
+    error_chain! {
+        derive {
+            Debug, Clone;
+        }
+
+        options {
+            shared_cause;
+        }
+    }
+
+    let cause = io::Error::new(io::ErrorKind::NotFound, "not found");
+    let err = Error::with_chain(SharedCause::new(cause), "inner").chain_err(|| "top");
+    let copy = err.clone();
+
+    let cause_of = |e: &Error| -> *const () {
+        let inner = ::std::error::Error::source(e).unwrap();
+        let cause = ::std::error::Error::source(inner).unwrap();
+        assert!(cause.is::<io::Error>());
+        cause as *const _ as *const ()
+    };
+    assert_eq!(cause_of(&err), cause_of(&copy));
+    let messages: Vec<String> = copy.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["top", "inner", "not found"]);
+
+    let unshared = Error::with_chain(io::Error::new(io::ErrorKind::NotFound, "gone"), "top");
+    let copy = unshared.clone();
+    assert_eq!(copy.iter().nth(1).unwrap().to_string(), "gone");
+}
+
+#[test]
+fn clone_boxed_chain() {
+    use std::io;
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let root = io::Error::new(io::ErrorKind::NotFound, "root");
+    let err = Error::with_chain(root, "inner").chain_err(|| "top");
+    let original: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    let snapshot = err.clone_boxed_chain();
+    drop(err);
+    let messages: Vec<String> = snapshot.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, original);
+    assert_eq!(messages, vec!["top", "inner", "root"]);
+    assert_eq!(Error::from("alone").clone_boxed_chain().len(), 1);
+}
+
+#[test]
+fn foreign_causes() {
+    use std::{error, fmt, io};
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    #[derive(Debug)]
+    struct Wrapper(Error);
+
+    impl fmt::Display for Wrapper {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("wrapper")
+        }
+    }
+
+    impl error::Error for Wrapper {
+        fn source(&self) -> Option<&(error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    let root = io::Error::new(io::ErrorKind::NotFound, "root");
+    let inner = Error::with_chain(root, "inner");
+    let err = Error::with_chain(Wrapper(inner), "top");
+    let foreign: Vec<String> = err.foreign_causes().map(|e| e.to_string()).collect();
+    assert_eq!(foreign, vec!["wrapper", "root"]);
+    assert!(err.foreign_causes().nth(1).unwrap().is::<io::Error>());
+
+    let foreign = Error::with_chain(io::Error::new(io::ErrorKind::NotFound, "root"), "top");
+    assert_eq!(foreign.foreign_causes().count(), 1);
+    assert_eq!(Error::from("alone").foreign_causes().count(), 0);
+}
+
+#[test]
+fn chain_nested() {
+    use std::{fmt, io};
+
+    error_chain! {}
+
+    let inner: ::std::result::Result<::std::result::Result<u8, fmt::Error>, io::Error> =
+        Ok(Err(fmt::Error));
+    let err = inner.chain_nested(|| "formatting failed").unwrap_err();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["formatting failed".to_owned(), fmt::Error.to_string()]);
+
+    let outer: ::std::result::Result<::std::result::Result<u8, fmt::Error>, io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+    let err = outer.chain_nested(|| "reading failed").unwrap_err();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["reading failed", "not found"]);
+
+    let ok: ::std::result::Result<::std::result::Result<u8, fmt::Error>, io::Error> = Ok(Ok(3));
+    assert_eq!(ok.chain_nested(|| "unused").unwrap(), 3);
+}
+
+#[test]
+fn err_kind() {
+    error_chain! {
+        errors {
+            Busy
+        }
+    }
+
+    let res: Result<()> = Err(ErrorKind::Busy.into());
+    match res.err_kind() {
+        Some(&ErrorKind::Busy) => {}
+        kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    assert!(res.is_err());
+
+    let res: Result<()> = Ok(());
+    assert!(res.err_kind().is_none());
+
+    let res: ::std::result::Result<(), ::std::fmt::Error> = Err(::std::fmt::Error);
+    assert!(res.err_kind().is_none());
+}
+
+#[test]
+fn rewrap() {
+    error_chain! {
+        errors {
+            Request
+        }
+    }
+
+    let err = Error::from("root").chain_err(|| "middle");
+    let backtrace = err.backtrace().map(|b| b as *const _);
+    let err = err.rewrap(ErrorKind::Request);
+    match *err.kind() {
+        ErrorKind::Request => {}
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["Request", "middle", "root"]);
+    assert_eq!(err.backtrace().map(|b| b as *const _), backtrace);
+}
+
+#[test]
+fn groups() {
+    error_chain! {
+        errors {
+            Other
+        }
+
+        groups {
+            Network(NetworkKind) {
+                Timeout {
+                    description("timed out")
+                }
+                Refused(host: String) {
+                    description("connection refused")
+                    display("connection refused by {}", host)
+                }
+            }
+            Parse(ParseKind) {
+                Eof
+            }
+        }
+    }
+
+    fn connect() -> Result<()> {
+        bail!(NetworkKind::Refused("example.com".into()))
+    }
+
+    let err = connect().unwrap_err();
+    match *err.kind() {
+        ErrorKind::Network(NetworkKind::Refused(ref host)) => assert_eq!(host, "example.com"),
+        _ => panic!("expected a refused connection"),
+    }
+    assert_eq!(err.to_string(), "connection refused by example.com");
+    assert_eq!(err.description(), "connection refused");
+    assert_eq!(err.kind_path(), "Network.Refused");
+
+    let kind: ErrorKind = ParseKind::Eof.into();
+    assert_eq!(kind.to_string(), "Eof");
+    assert_eq!(ErrorKind::from(NetworkKind::Timeout).description(), "timed out");
+}
+
+#[test]
+fn ok_or_log() {
+    error_chain! {}
+
+    let mut logged = Vec::new();
+    let res: Result<()> = Err(Error::from("root").chain_err(|| "top"));
+    assert_eq!(res.ok_or_log(|e| logged = e.iter().map(|e| e.to_string()).collect()), None);
+    assert_eq!(logged, vec!["top", "root"]);
+
+    let res: Result<u32> = Ok(1);
+    assert_eq!(res.ok_or_log(|_| panic!("not an error")), Some(1));
+}
+
+#[test]
+fn global_context() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    error_chain::push_global_context("storage");
+    error_chain::push_global_context("compaction");
+    let err = Error::from("boom");
+    assert_eq!(error_chain::pop_global_context(), Some("compaction".to_owned()));
+    let chained = Error::with_chain(err, "failed");
+    assert_eq!(error_chain::pop_global_context(), Some("storage".to_owned()));
+    assert_eq!(error_chain::pop_global_context(), None);
+
+    assert_eq!(chained.global_context(), &["storage".to_owned()]);
+    let cause = ::std::error::Error::source(&chained).unwrap().downcast_ref::<Error>().unwrap();
+    assert_eq!(cause.global_context(), &["storage".to_owned(), "compaction".to_owned()]);
+    assert_eq!(cause.display_chain_no_backtrace().to_string(), "Error: boom\n");
+    assert!(cause.display_detailed().to_string()
+        .starts_with("Error: boom\nContext: storage > compaction\n"));
+    assert!(Error::from("boom").global_context().is_empty());
+}
+
+#[test]
+fn mapped_foreign_links() {
+    use std::num::ParseIntError;
+
+    error_chain! {
+        foreign_links {
+            Parse(ParseIntError) as ErrorKind::InvalidInput;
+            Io(::std::io::Error);
+        }
+
+        errors {
+            InvalidInput {
+                description("invalid input")
+            }
+        }
+    }
+
+    fn parse(s: &str) -> Result<u32> {
+        Ok(s.parse()?)
+    }
+
+    let err = parse("nope").unwrap_err();
+    match *err.kind() {
+        ErrorKind::InvalidInput => {}
+        _ => panic!("expected invalid input"),
+    }
+    assert_eq!(err.to_string(), "invalid input");
+    assert_eq!(err.iter().nth(1).unwrap().to_string(), "invalid digit found in string");
+    assert!(err.has_foreign_root());
+
+    let err = Error::from(::std::io::Error::new(::std::io::ErrorKind::NotFound, "missing"));
+    match *err.kind() {
+        ErrorKind::Io(_) => {}
+        _ => panic!("expected the io error"),
+    }
+}
+
+#[test]
+fn downcast_root() {
+    use error_chain::ChainedError;
+
+    error_chain! {
+        errors {
+            Disk
+        }
+    }
+
+    let err = Error::from(ErrorKind::Disk).chain_err(|| "mid").chain_err(|| "top");
+    let root = err.downcast_root::<Error>().unwrap();
+    match *root.kind() {
+        ErrorKind::Disk => {}
+        _ => panic!("expected the disk error"),
+    }
+
+    let foreign = ::std::io::Error::new(::std::io::ErrorKind::NotFound, "missing");
+    let err = Error::with_chain(foreign, "top");
+    assert!(err.downcast_root::<Error>().is_none());
+}
+
+#[test]
+fn filter_err() {
+    error_chain! {
+        errors {
+            AlreadyExists
+            PermissionDenied
+        }
+    }
+
+    fn already_exists(e: &Error) -> bool {
+        matches!(*e.kind(), ErrorKind::AlreadyExists)
+    }
+
+    let res: Result<u32> = Err(ErrorKind::AlreadyExists.into());
+    assert_eq!(res.filter_err(already_exists, 0).unwrap(), 0);
+
+    let res: Result<u32> = Err(ErrorKind::PermissionDenied.into());
+    match *res.filter_err(already_exists, 0).unwrap_err().kind() {
+        ErrorKind::PermissionDenied => {}
+        _ => panic!("expected the permission error"),
+    }
+
+    let res: Result<u32> = Ok(1);
+    assert_eq!(res.filter_err(already_exists, 0).unwrap(), 1);
+}
+
+#[test]
+fn flat_links() {
+    mod utils {
+        error_chain! {
+            errors {
+                BadStuff {
+                    description("bad stuff")
+                    display("bad stuff happened")
+                }
+            }
+        }
+    }
+
+    error_chain! {
+        flat_links {
+            Utils(utils::Error, utils::ErrorKind);
+        }
+    }
+
+    let child = utils::Error::from(utils::ErrorKind::BadStuff).chain_err(|| "utils failed");
+    let err = Error::from(child);
+    assert_eq!(err.kind().message(), Some("utils failed"));
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["utils failed", "bad stuff happened"]);
+
+    let kind = ErrorKind::from(utils::ErrorKind::BadStuff);
+    assert_eq!(kind.message(), Some("bad stuff happened"));
+}
+
+#[test]
+fn best_description() {
+    use error_chain::ChainedError;
+    use std::fmt;
+
+    #[derive(Debug)]
+    struct NoDescription;
+
+    impl fmt::Display for NoDescription {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "no description")
+        }
+    }
+
+    impl ::std::error::Error for NoDescription {}
+
+    error_chain! {
+        errors {
+            OperationFailed {
+                description("operation failed")
+            }
+            DiskFull {
+                description("the disk is full")
+            }
+        }
+    }
+
+    let err = Error::from(ErrorKind::DiskFull).chain_err(|| ErrorKind::OperationFailed);
+    assert_eq!(err.best_description(), "the disk is full");
+
+    let err = Error::with_chain(NoDescription, ErrorKind::DiskFull)
+        .chain_err(|| ErrorKind::OperationFailed);
+    assert_eq!(err.best_description(), "the disk is full");
+}
+
+#[test]
+fn boxed_chain() {
+    use error_chain::{BoxedChainError, ChainedError};
+
+    mod storage {
+        error_chain! {
+            errors {
+                DiskFull
+            }
+        }
+    }
+
+    mod network {
+        error_chain! {
+            errors {
+                Timeout
+            }
+        }
+    }
+
+    let errors: Vec<BoxedChainError> = vec![
+        storage::Error::from(storage::ErrorKind::DiskFull).chain_err(|| "write failed").into_boxed_chain(),
+        network::Error::from(network::ErrorKind::Timeout).into_boxed_chain(),
+    ];
+
+    let kinds: Vec<&str> = errors.iter().map(|e| e.kind_name()).collect();
+    assert_eq!(kinds, vec!["Msg", "Timeout"]);
+    assert_eq!(errors[0].iter().count(), 2);
+    assert!(errors[0].display_chain().to_string().starts_with("Error: write failed\nCaused by: DiskFull\n"));
+    assert!(errors[1].display_chain().to_string().starts_with("Error: Timeout\n"));
+}
+
+#[test]
+fn public_display() {
+    mod auth {
+        error_chain! {
+            errors {
+                InvalidToken(token: String) {
+                    display("invalid token '{}'", token)
+                    public_display("invalid token")
+                }
+            }
+        }
+    }
+
+    error_chain! {
+        links {
+            Auth(auth::Error, auth::ErrorKind);
+        }
+
+        errors {
+            Locked(user: String, reason: String) {
+                display("account of {} locked: {}", user, reason)
+                public_display("account of {} locked", user)
+            }
+            Internal
+        }
+    }
+
+    let err = Error::from(ErrorKind::Locked("alice".into(), "3 failed attempts".into()));
+    assert_eq!(err.to_string(), "account of alice locked: 3 failed attempts");
+    assert_eq!(err.public_display(), "account of alice locked");
+
+    let err: Error = auth::Error::from(auth::ErrorKind::InvalidToken("s3cr3t".into())).into();
+    assert_eq!(err.to_string(), "invalid token 's3cr3t'");
+    assert_eq!(err.public_display(), "invalid token");
+
+    assert_eq!(ErrorKind::Internal.public_display(), "an error occurred");
+    assert_eq!(Error::from("secret path").public_display(), "an error occurred");
+}
+
+#[test]
+fn borrowed_family() {
+    mod parse {
+        error_chain! {
+            types {
+                Error<'s>, ErrorKind<'s>, ResultExt, Result;
+            }
+
+            errors {
+                Token(t: &'s str) {
+                    description("unexpected token")
+                    display("unexpected token: '{}'", t)
+                }
+                Eof
+            }
+        }
+
+        pub fn expect_eof<'a>(input: &'a str) -> Result<'a, ()> {
+            match input.split_whitespace().next() {
+                Some(t) => Err(ErrorKind::Token(t).into()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    let input = String::from("  let x");
+    let err = parse::expect_eof(&input).unwrap_err();
+    match *err.kind() {
+        parse::ErrorKind::Token(t) => assert_eq!(t, "let"),
+        _ => panic!("Unexpected error kind: {:?}", err),
+    }
+    assert_eq!(err.to_string(), "unexpected token: 'let'");
+    assert_eq!(err.variant_name(), "Token");
+
+    let err = parse::Error::from("custom");
+    assert_eq!(err.message(), Some("custom"));
+    assert!(parse::expect_eof(" ").is_ok());
+}
+
+#[test]
+fn scoped_family() {
+    use std::fmt;
+
+    mod scoped {
+        error_chain! {
+            types {
+                Error<'s>, ErrorKind<'s>, ResultExt, Result;
+            }
+
+            errors {
+                Field(name: &'s str) {
+                    description("invalid field")
+                    display("invalid field '{}'", name)
+                }
+            }
+
+            options {
+                scoped;
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Invalid<'a>(&'a str);
+
+    impl<'a> fmt::Display for Invalid<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "invalid value '{}'", self.0)
+        }
+    }
+
+    impl<'a> ::std::error::Error for Invalid<'a> {}
+
+    fn check<'a>(name: &'a str, cause: &'a Invalid<'a>) -> scoped::Result<'a, ()> {
+        Err(scoped::Error::with_chain(cause, scoped::ErrorKind::Field(name)))
+    }
+
+    let input = String::from("age=-3");
+    let (name, value) = input.split_at(3);
+    let cause = Invalid(&value[1..]);
+    let err = check(name, &cause).unwrap_err();
+    assert_eq!(err.to_string(), "invalid field 'age'");
+    assert_eq!(err.scoped_cause().unwrap().to_string(), "invalid value '-3'");
+    assert!(scoped::Error::from("custom").scoped_cause().is_none());
+}
+
+#[test]
+fn render_with() {
+    use error_chain::{AnyChained, ChainRenderer, ChainedError, InlineRenderer, NumberedRenderer,
+                      TreeRenderer};
+
+    struct KindsRenderer;
+
+    impl ChainRenderer for KindsRenderer {
+        fn render(&self, err: &AnyChained) -> String {
+            format!("{} ({} errors)", err.any_kind_name(), err.any_iter().count())
+        }
+    }
+
+    error_chain! {
+        errors {
+            Root
+        }
+    }
+
+    let err = Error::from(ErrorKind::Root).chain_err(|| "top");
+    assert_eq!(err.render_with(&KindsRenderer), "Msg (2 errors)");
+    assert_eq!(err.render_with(&InlineRenderer), "top: Root");
+    assert_eq!(err.render_with(&TreeRenderer), "Error: top\n  caused by: Root\n");
+    assert_eq!(err.render_with(&NumberedRenderer), "0: top\n1: Root\n");
+    assert_eq!(err.into_boxed_chain().render_with(&InlineRenderer), "top: Root");
+}
+
+#[test]
+fn generic_links() {
+    mod generic {
+        use std::error;
+        use std::fmt;
+        use error_chain::State;
+
+        #[derive(Debug)]
+        pub enum ErrorKind<T> {
+            Value(T),
+        }
+
+        impl<T: fmt::Debug> ErrorKind<T> {
+            pub fn description(&self) -> &str {
+                "value error"
+            }
+
+            pub fn kind_path(&self) -> String {
+                "Value".to_owned()
+            }
+
+            pub fn public_display(&self) -> String {
+                "invalid value".to_owned()
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Display for ErrorKind<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    ErrorKind::Value(ref v) => write!(f, "bad value: {:?}", v),
+                }
+            }
+        }
+
+        #[derive(Debug)]
+        pub struct Error<T>(pub ErrorKind<T>, pub State);
+
+        impl<T: fmt::Debug> error::Error for Error<T> {
+            fn description(&self) -> &str {
+                self.0.description()
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Display for Error<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    }
+
+    error_chain! {
+        links {
+            Generic(generic::Error<u32>, generic::ErrorKind<u32>);
+        }
+    }
+
+    let err: Error = generic::Error(generic::ErrorKind::Value(3), Default::default()).into();
+    match *err.kind() {
+        ErrorKind::Generic(generic::ErrorKind::Value(3)) => {}
+        _ => panic!("unexpected kind: {:?}", err),
+    }
+    assert_eq!(err.to_string(), "bad value: 3");
+    let _: ErrorKind = generic::ErrorKind::Value(3).into();
+}
+
+#[test]
+fn description_static() {
+    error_chain! {
+        errors {
+            HttpStatus(e: u32) {
+                description("http request returned an unsuccessful status code")
+                display("http request returned an unsuccessful status code: {}", e)
+            }
+            NoDescription
+        }
+    }
+
+    assert_eq!(ErrorKind::HttpStatus(404).description_static(),
+               "http request returned an unsuccessful status code");
+    assert_eq!(ErrorKind::NoDescription.description_static(), "NoDescription");
+    assert_eq!(ErrorKind::Msg("msg".into()).description_static(), "Msg");
+}
+
+#[test]
+fn kind_as_error() {
+    error_chain! {
+        errors {
+            Timeout {
+                description("timed out")
+                display("timed out after {}s", 3)
+            }
+        }
+    }
+
+    fn fails() -> ::std::result::Result<(), Box<::std::error::Error>> {
+        Err(Box::new(ErrorKind::Timeout))
+    }
+
+    let err = fails().unwrap_err();
+    assert_eq!(err.to_string(), "timed out after 3s");
+    assert!(err.source().is_none());
+    assert!(err.downcast_ref::<ErrorKind>().is_some());
+}
+
+#[test]
+fn descriptions() {
+    error_chain! {
+        errors {
+            HttpStatus(e: u32) {
+                description("http request returned an unsuccessful status code")
+                display("http request returned an unsuccessful status code: {}", e)
+            }
+            /// Not described.
+            NoDescription
+        }
+    }
+
+    assert!(ErrorKind::DESCRIPTIONS.contains(
+        &("HttpStatus", "http request returned an unsuccessful status code")));
+    assert!(ErrorKind::DESCRIPTIONS.contains(&("NoDescription", "NoDescription")));
+    assert!(ErrorKind::DESCRIPTIONS.contains(&("Msg", "Msg")));
+}
+
+#[test]
+fn detailed_description() {
+    error_chain! {
+        errors {
+            HttpStatus(e: u32) {
+                description("http request returned an unsuccessful status code")
+                display("http request returned an unsuccessful status code: {}", e)
+            }
+            InvalidToolchainName(t: String) {
+                display("invalid toolchain name: '{}'", t)
+            }
+        }
+    }
+
+    let kind = ErrorKind::HttpStatus(404);
+    assert_eq!(kind.detailed_description(), kind.description());
+
+    let kind = ErrorKind::InvalidToolchainName("nightly-foo".into());
+    assert_eq!(kind.description(), "InvalidToolchainName");
+    assert_eq!(kind.detailed_description(), "invalid toolchain name: 'nightly-foo'");
+}
+
+#[test]
+fn is_retriable() {
+    error_chain! {
+        errors {
+            Timeout {
+                retriable
+                description("timed out")
+            }
+            Refused(host: String) {
+                description("connection refused"),
+                retriable,
+                display("connection refused by {}", host)
+            }
+            NotFound {
+                description("not found")
+            }
+        }
+    }
+
+    assert!(ErrorKind::Timeout.is_retriable());
+    assert!(ErrorKind::Refused("example.com".into()).is_retriable());
+    assert_eq!(ErrorKind::Refused("example.com".into()).to_string(),
+               "connection refused by example.com");
+    assert!(!ErrorKind::NotFound.is_retriable());
+    assert!(!ErrorKind::Msg("msg".into()).is_retriable());
+}
+
+#[test]
+fn kind_try_from_code() {
+    use std::convert::TryFrom;
+
+    error_chain! {
+        errors {
+            NotFound {
+                description("not found")
+                code(404)
+            }
+            HttpStatus(e: u32) {
+                description("http error")
+                code(500)
+            }
+            NoCode
+        }
+    }
+
+    match ErrorKind::try_from(404) {
+        Ok(ErrorKind::NotFound) => {}
+        _ => panic!("expected NotFound"),
+    }
+    assert_eq!(ErrorKind::try_from(500).unwrap_err(), 500);
+    assert_eq!(ErrorKind::try_from(1).unwrap_err(), 1);
+}
+
+#[test]
+fn kind_try_from_without_codes() {
+    use std::convert::TryFrom;
+
+    error_chain! {
+        errors {
+            NotFound {
+                description("not found")
+            }
+        }
+    }
+
+    // Without declared codes, no `TryFrom<i64>` is generated, so one can be
+    // written by hand.
+    impl TryFrom<i64> for ErrorKind {
+        type Error = i64;
+
+        fn try_from(code: i64) -> ::std::result::Result<Self, i64> {
+            match code {
+                404 => Ok(ErrorKind::NotFound),
+                _ => Err(code),
+            }
+        }
+    }
+
+    match ErrorKind::try_from(404) {
+        Ok(ErrorKind::NotFound) => {}
+        _ => panic!("expected NotFound"),
+    }
+}
+
+#[test]
+fn display_with_code() {
+    error_chain! {
+        errors {
+            NotFound {
+                description("not found")
+                code(404)
+            }
+            NoCode {
+                description("no code")
+            }
+        }
+
+        options {
+            display_with_code;
+        }
+    }
+
+    assert_eq!(ErrorKind::NotFound.code(), Some(404));
+    assert_eq!(ErrorKind::NoCode.code(), None);
+    assert_eq!(Error::from(ErrorKind::NotFound).to_string(), "[404] not found");
+    assert_eq!(Error::from(ErrorKind::NoCode).to_string(), "no code");
+}
+
+#[test]
+fn display_without_code() {
+    error_chain! {
+        errors {
+            NotFound {
+                description("not found")
+                code(404)
+            }
+        }
+    }
+
+    assert_eq!(ErrorKind::NotFound.to_string(), "not found");
+}
+
+#[test]
+fn summarize() {
+    use error_chain::ChainedError;
+
+    error_chain! {}
+
+    let err = Error::from("root").chain_err(|| "middle").chain_err(|| "boom");
+    assert_eq!(err.summarize(), "boom (+2 causes)");
+    assert_eq!(Error::from("boom").summarize(), "boom");
+}
+
+#[test]
+fn foreign_link_source() {
+    use std::error::Error as StdError;
+    use std::fmt;
+
+    #[derive(Debug)]
+    pub struct Outer(Inner);
+
+    #[derive(Debug)]
+    pub struct Inner;
+
+    impl StdError for Outer {
+        fn source(&self) -> Option<&(StdError + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "outer")
+        }
+    }
+
+    impl StdError for Inner {}
+
+    impl fmt::Display for Inner {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "inner")
+        }
+    }
+
+    error_chain! {
+        foreign_links {
+            Outer(Outer);
+        }
+    }
+
+    let err = Error::from(Outer(Inner));
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["outer", "inner"]);
+    assert_eq!(err.source().unwrap().to_string(), "inner");
+
+    let err = err.chain_err(|| "top");
+    assert_eq!(err.source().unwrap().source().unwrap().to_string(), "inner");
+}
+
+#[test]
+fn transparent_foreign_links() {
+    use std::io;
+
+    error_chain! {
+        transparent_foreign_links {
+            Io(io::Error);
+        }
+    }
+
+    let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "no file"));
+    match *err.kind() {
+        ErrorKind::Msg(ref s) => assert_eq!(s, "no file"),
+    }
+    let cause = ::std::error::Error::source(&err).unwrap();
+    assert!(cause.downcast_ref::<io::Error>().is_some());
+}
+
+#[test]
+fn error_accumulator() {
+    use std::io;
+    use error_chain::ErrorAccumulator;
+
+    error_chain! {}
+
+    let errors: ErrorAccumulator<Error> = ErrorAccumulator::new();
+    assert!(errors.into_error().is_none());
+
+    let mut errors = ErrorAccumulator::new();
+    errors.push(Error::from("first"));
+    errors.extend(vec![Error::from("second"), Error::from("root").chain_err(|| "third")]);
+    let err = errors.into_error().unwrap();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["first", "second", "third", "root"]);
+
+    let mut errors = ErrorAccumulator::new();
+    errors.push(Error::from("inner").chain_err(|| "first"));
+    errors.push(Error::with_chain(io::Error::new(io::ErrorKind::NotFound, "missing"), "second"));
+    errors.push(Error::from("third"));
+    let err = errors.into_error().unwrap();
+    let messages: Vec<String> = err.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec!["first", "inner", "second", "missing", "third"]);
+}
+
+#[test]
+fn debug_as_chain() {
+    mod chain {
+        error_chain! {
+            options {
+                debug_as_chain;
+            }
+        }
+    }
+
+    let err = chain::Error::from("root").chain_err(|| "top");
+    let debug = format!("{:?}", err);
+    assert!(debug.starts_with("Error: top\nCaused by: root\n"), "{}", debug);
+
+    error_chain! {}
+
+    let debug = format!("{:?}", Error::from("root").chain_err(|| "top"));
+    assert!(debug.starts_with("Error(Msg(\"top\"), State {"), "{}", debug);
+}
+
+#[test]
+fn display_full_chain() {
+    use std::io;
+
+    error_chain! {
+        options {
+            display_full_chain;
+        }
+    }
+
+    let root = io::Error::new(io::ErrorKind::NotFound, "root");
+    let err = Error::with_chain(root, "inner").chain_err(|| "top");
+    assert_eq!(err.to_string(), "top: inner: root");
+    assert_eq!(Error::from("alone").to_string(), "alone");
+}
+
+#[test]
+fn bail_kind() {
+    error_chain! {
+        errors {
+            Failed(s: String) {
+                display("failed: {}", s)
+            }
+        }
+
+        groups {
+            Network(NetworkKind) {
+                Timeout
+            }
+        }
+
+        options {
+            bail_kind(Failed);
+        }
+    }
+
+    fn formatted(n: u8) -> Result<()> {
+        bail!("bad number: {}", n)
+    }
+
+    fn literal() -> Result<()> {
+        bail!("literal")
+    }
+
+    match *formatted(3).unwrap_err().kind() {
+        ErrorKind::Failed(ref s) => assert_eq!(s, "bad number: 3"),
+        ref kind => panic!("Unexpected error kind: {:?}", kind),
+    }
+    assert_eq!(literal().unwrap_err().message(), Some("literal"));
+}
+
+#[test]
+#[cfg(not(feature = "context-trace"))]
+fn bail_into_foreign_error() {
+    #[derive(Debug)]
+    struct CustomError(String);
+
+    impl From<String> for CustomError {
+        fn from(s: String) -> Self {
+            CustomError(s)
+        }
+    }
+
+    fn formatted(n: u8) -> ::std::result::Result<(), CustomError> {
+        bail!("bad number: {}", n)
+    }
+
+    assert_eq!(formatted(3).unwrap_err().0, "bad number: 3");
+}
+
+#[test]
+fn result_with_default_error() {
+    error_chain! {}
+
+    fn default_error() -> Result<i32> {
+        bail!("bailed")
+    }
+
+    fn io_error() -> Result<i32, ::std::io::Error> {
+        Err(::std::io::Error::new(::std::io::ErrorKind::NotFound, "io"))
+    }
+
+    let err: Error = default_error().unwrap_err();
+    assert_eq!(err.to_string(), "bailed");
+    let err: Error = io_error().chain_err(|| "chained").unwrap_err();
+    assert_eq!(err.iter().count(), 2);
+}
+
+mod cfg_gated {
+    error_chain! {
+        cfg(feature = "backtrace")
+
+        errors { Gated }
+    }
+
+    // Conflicts with the generated `Error` unless it is gated out.
+    #[cfg(not(feature = "backtrace"))]
+    pub struct Error;
+}
+
+#[test]
+fn cfg_gated() {
+    #[cfg(feature = "backtrace")]
+    {
+        let err: cfg_gated::Error = cfg_gated::ErrorKind::Gated.into();
+        assert_eq!(err.to_string(), "Gated");
+    }
+    #[cfg(not(feature = "backtrace"))]
+    {
+        let _ = cfg_gated::Error;
+    }
+}
@@ -0,0 +1,88 @@
+//! Tests which need `RUST_BACKTRACE` to be unset. They live in their own
+//! binary so they don't race with tests setting it.
+#![cfg(feature = "backtrace")]
+
+#[macro_use]
+extern crate error_chain;
+
+use error_chain::Backtrace;
+use std::io;
+
+error_chain! {}
+
+#[test]
+fn with_backtrace() {
+    ::std::env::remove_var("RUST_BACKTRACE");
+
+    assert!(Error::from("boom").backtrace().is_none());
+
+    let backtrace = Backtrace::new();
+    let frames = backtrace.frames().len();
+    let res: Result<()> = Err("boom".into());
+    let err = res.with_backtrace(backtrace).unwrap_err();
+    assert_eq!(err.backtrace().unwrap().frames().len(), frames);
+
+    let res: ::std::result::Result<(), io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "file not found"));
+    let err = res.with_backtrace(Backtrace::new()).unwrap_err();
+    assert_eq!(err.to_string(), "file not found");
+    assert!(err.backtrace().is_some());
+}
+
+#[test]
+fn ensure_backtrace() {
+    ::std::env::remove_var("RUST_BACKTRACE");
+
+    let mut err = Error::from("boom");
+    assert!(err.backtrace().is_none());
+    err.ensure_backtrace();
+    assert!(err.backtrace().is_some());
+}
+
+#[test]
+fn backtrace_string() {
+    use error_chain::ChainedError;
+
+    ::std::env::remove_var("RUST_BACKTRACE");
+
+    let mut err = Error::from("boom");
+    assert_eq!(err.backtrace_string(), None);
+    err.ensure_backtrace();
+    let backtrace = err.backtrace_string().unwrap();
+    assert_eq!(backtrace, format!("{:?}", err.backtrace().unwrap()));
+    assert!(!backtrace.is_empty());
+}
+
+mod always {
+    error_chain! {
+        foreign_links {
+            Io(::std::io::Error);
+        }
+
+        options {
+            always_backtrace;
+        }
+    }
+}
+
+#[test]
+fn always_backtrace() {
+    ::std::env::remove_var("RUST_BACKTRACE");
+
+    assert!(always::Error::from("boom").backtrace().is_some());
+    let err: always::Error = io::Error::new(io::ErrorKind::NotFound, "not found").into();
+    assert!(err.backtrace().is_some());
+    let res: ::std::result::Result<(), io::Error> =
+        Err(io::Error::new(io::ErrorKind::NotFound, "not found"));
+    assert!(always::ResultExt::chain_err(res, || "chained").unwrap_err().backtrace().is_some());
+
+    let mut err = always::Error::from("boom");
+    err.reset_cause_and_backtrace();
+    assert!(err.backtrace().is_none());
+    let res: ::std::result::Result<(), always::Error> = Err(err);
+    assert!(always::ResultExt::trace_err(res).unwrap_err().backtrace().is_some());
+
+    ::std::env::set_var("RUST_BACKTRACE", "0");
+    assert!(always::Error::from("boom").backtrace().is_none());
+    ::std::env::remove_var("RUST_BACKTRACE");
+}